@@ -0,0 +1,564 @@
+use crate::bitboard::{BitBoard, MAX_TABLE_LENGTH};
+use crate::components::Position;
+use crate::grid::{BoardModel, Direction, Grid};
+use crate::spawn::SpawnRules;
+use std::cell::Cell;
+use std::convert::TryFrom;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+/// How good a board looks to the search. Values should be
+/// positive, a board with no moves left is worth 0.
+pub trait Evaluate<B = Grid> {
+    fn evaluate(&self, board: &B) -> f64;
+}
+
+impl<B, F: Fn(&B) -> f64> Evaluate<B> for F {
+    fn evaluate(&self, board: &B) -> f64 {
+        self(board)
+    }
+}
+
+/// The usual 2048 heuristic, scored a row or column at a
+/// time on the log2 of each tile: empty cells and
+/// possible merges are good, lines that go up and down
+/// and big tiles not yet merged are bad.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Heuristic {
+    // keeps every line above 0
+    pub base: f64,
+    pub empty: f64,
+    pub merges: f64,
+    pub monotonicity: f64,
+    pub monotonicity_power: f64,
+    pub sum: f64,
+    pub sum_power: f64,
+}
+
+impl Default for Heuristic {
+    fn default() -> Self {
+        Heuristic {
+            base: 200_000.0,
+            empty: 270.0,
+            merges: 700.0,
+            monotonicity: 47.0,
+            monotonicity_power: 4.0,
+            sum: 11.0,
+            sum_power: 3.5,
+        }
+    }
+}
+
+impl Heuristic {
+    /// Scores one line, given as the log2 of each tile
+    /// with 0 for an empty cell.
+    pub fn line(&self, ranks: &[u32]) -> f64 {
+        let mut sum = 0.0;
+        let mut empty = 0;
+        let mut merges = 0;
+        // equal tiles in a row, not counting empty cells
+        let mut previous = 0;
+        let mut run = 0;
+        for &rank in ranks {
+            sum += f64::from(rank).powf(self.sum_power);
+            if rank == 0 {
+                empty += 1;
+                continue;
+            }
+            if rank == previous {
+                run += 1;
+            } else {
+                if run > 0 {
+                    merges += 1 + run;
+                }
+                run = 0;
+                previous = rank;
+            }
+        }
+        if run > 0 {
+            merges += 1 + run;
+        }
+
+        let mut decreasing = 0.0;
+        let mut increasing = 0.0;
+        for pair in ranks.windows(2) {
+            let (a, b) = (f64::from(pair[0]), f64::from(pair[1]));
+            let (a, b) = (
+                a.powf(self.monotonicity_power),
+                b.powf(self.monotonicity_power),
+            );
+            if a > b {
+                decreasing += a - b;
+            } else {
+                increasing += b - a;
+            }
+        }
+
+        self.base + self.empty * f64::from(empty) + self.merges * f64::from(merges)
+            - self.monotonicity * f64::min(decreasing, increasing)
+            - self.sum * sum
+    }
+}
+
+impl<B: BoardModel> Evaluate<B> for Heuristic {
+    fn evaluate(&self, board: &B) -> f64 {
+        let rank = |x, y| board.get(Position { x, y }).map_or(0, log2);
+        let rows = (0..board.height()).map(|y| {
+            let ranks: Vec<u32> = (0..board.width()).map(|x| rank(x, y)).collect();
+            self.line(&ranks)
+        });
+        let columns = (0..board.width()).map(|x| {
+            let ranks: Vec<u32> = (0..board.height()).map(|y| rank(x, y)).collect();
+            self.line(&ranks)
+        });
+        rows.chain(columns).sum()
+    }
+}
+
+/// `Heuristic` with the score of every line that fits in a
+/// `BitBoard` table worked out the first time it's needed,
+/// which makes evaluating a `BitBoard` a few lookups.
+#[derive(Debug, Clone)]
+pub struct HeuristicTable {
+    heuristic: Heuristic,
+    lines: [OnceLock<Vec<f64>>; (MAX_TABLE_LENGTH - 1) as usize],
+}
+
+impl HeuristicTable {
+    pub fn new(heuristic: Heuristic) -> Self {
+        HeuristicTable {
+            heuristic,
+            lines: Default::default(),
+        }
+    }
+
+    pub fn heuristic(&self) -> &Heuristic {
+        &self.heuristic
+    }
+
+    /// Works out the lines of a board this size now,
+    /// rather than during the first search that needs them.
+    pub fn prepare(&self, width: u8, height: u8) {
+        self.table(width);
+        self.table(height);
+    }
+
+    fn table(&self, length: u8) -> Option<&Vec<f64>> {
+        if !(2..=MAX_TABLE_LENGTH).contains(&length) {
+            return None;
+        }
+        Some(self.lines[usize::from(length - 2)].get_or_init(|| {
+            (0..1 << (4 * u32::from(length)))
+                .map(|line| self.heuristic.line(&ranks(line, length)))
+                .collect()
+        }))
+    }
+
+    // a row or column packed like `BitBoard::rows`
+    fn line(&self, line: u32, length: u8) -> f64 {
+        match self.table(length) {
+            Some(table) => table[line as usize],
+            None => self.heuristic.line(&ranks(line, length)),
+        }
+    }
+}
+
+fn ranks(line: u32, length: u8) -> Vec<u32> {
+    (0..length).map(|cell| (line >> (cell * 4)) & 0xf).collect()
+}
+
+impl Default for HeuristicTable {
+    fn default() -> Self {
+        HeuristicTable::new(Heuristic::default())
+    }
+}
+
+impl Evaluate<Grid> for HeuristicTable {
+    fn evaluate(&self, grid: &Grid) -> f64 {
+        self.heuristic.evaluate(grid)
+    }
+}
+
+impl Evaluate<BitBoard> for HeuristicTable {
+    fn evaluate(&self, board: &BitBoard) -> f64 {
+        let rows = board.rows().map(|row| self.line(row, board.width()));
+        let columns = board
+            .columns()
+            .map(|column| self.line(column, board.height()));
+        rows.chain(columns).sum()
+    }
+}
+
+/// The move worth the most out of some move values.
+pub fn best(values: &[(Direction, f64)]) -> Option<(Direction, f64)> {
+    values
+        .iter()
+        .copied()
+        .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
+}
+
+// tiles that aren't a power of two, e.g. from `--spawn 3:1`,
+// are ranked as the power of two below them
+fn log2(value: u32) -> u32 {
+    31 - value.max(1).leading_zeros()
+}
+
+// how often, in chance nodes, a timed search looks at the
+// clock. Most of them are leaves that evaluate a board,
+// which is a lot slower than reading the clock.
+const CLOCK_INTERVAL: u32 = 16;
+
+// when a search has to give up, checked as it goes
+struct Budget {
+    deadline: Option<Instant>,
+    nodes: Cell<u32>,
+    out_of_time: Cell<bool>,
+}
+
+impl Budget {
+    fn unlimited() -> Self {
+        Budget::until(None)
+    }
+
+    fn until(deadline: Option<Instant>) -> Self {
+        Budget {
+            deadline,
+            nodes: Cell::new(0),
+            out_of_time: Cell::new(false),
+        }
+    }
+
+    fn spent(&self) -> bool {
+        let deadline = match self.deadline {
+            Some(deadline) if !self.out_of_time.get() => deadline,
+            _ => return self.out_of_time.get(),
+        };
+        let nodes = self.nodes.get().wrapping_add(1);
+        self.nodes.set(nodes);
+        if nodes % CLOCK_INTERVAL == 0 && Instant::now() >= deadline {
+            self.out_of_time.set(true);
+        }
+        self.out_of_time.get()
+    }
+}
+
+/// Searches every move and every tile that could spawn
+/// after it, `depth` moves ahead, and picks the move with
+/// the best expected evaluation. Works on any board, but
+/// a `BitBoard` is much quicker to search than a `Grid`.
+#[derive(Debug, Clone)]
+pub struct Expectimax<E = HeuristicTable> {
+    pub depth: u32,
+    /// Spawns less likely than this, counted from the
+    /// board being searched, are evaluated instead of
+    /// searched any deeper.
+    pub min_probability: f64,
+    rules: SpawnRules,
+    evaluation: E,
+}
+
+impl Expectimax {
+    pub fn new(depth: u32, rules: SpawnRules) -> Self {
+        Expectimax::with_evaluation(depth, rules, HeuristicTable::default())
+    }
+
+    /// Builds the tables a `BitBoard` of this size is
+    /// moved and evaluated with, which takes a moment for
+    /// 5 cell lines, so no search has to wait for them.
+    pub fn prepare(&self, width: u8, height: u8) {
+        BitBoard::prepare(width, height);
+        self.evaluation.prepare(width, height);
+    }
+}
+
+impl<E> Expectimax<E> {
+    pub fn with_evaluation(depth: u32, rules: SpawnRules, evaluation: E) -> Self {
+        assert!(depth > 0, "the search has to look at least one move ahead");
+        Expectimax {
+            depth,
+            min_probability: 0.0001,
+            rules,
+            evaluation,
+        }
+    }
+
+    /// The move with the best expected value, or `None`
+    /// if no move changes the board.
+    pub fn best_move<B: BoardModel>(&self, board: &B) -> Option<(Direction, f64)>
+    where
+        E: Evaluate<B>,
+    {
+        best(&self.move_values(board))
+    }
+
+    /// `best_move` for the game's own board, see
+    /// `grid_move_values`.
+    pub fn best_grid_move(&self, grid: &Grid) -> Option<(Direction, f64)>
+    where
+        E: Evaluate<Grid> + Evaluate<BitBoard>,
+    {
+        best(&self.grid_move_values(grid))
+    }
+
+    /// `move_values` for the game's own board, searched as a
+    /// `BitBoard` when it and every tile that can spawn fit
+    /// in one and merge the same way.
+    pub fn grid_move_values(&self, grid: &Grid) -> Vec<(Direction, f64)>
+    where
+        E: Evaluate<Grid> + Evaluate<BitBoard>,
+    {
+        match self.as_bitboard(grid) {
+            Some(board) => self.move_values(&board),
+            None => self.move_values(grid),
+        }
+    }
+
+    /// `move_values_within` for the game's own board, see
+    /// `grid_move_values`.
+    pub fn grid_move_values_within(
+        &self,
+        grid: &Grid,
+        limit: Duration,
+    ) -> (Vec<(Direction, f64)>, u32)
+    where
+        E: Evaluate<Grid> + Evaluate<BitBoard>,
+    {
+        match self.as_bitboard(grid) {
+            Some(board) => self.move_values_within(&board, limit),
+            None => self.move_values_within(grid, limit),
+        }
+    }
+
+    // `grid` as a `BitBoard`, if it and every tile that
+    // can spawn fit in one, and none of them is too big to
+    // merge there
+    fn as_bitboard(&self, grid: &Grid) -> Option<BitBoard> {
+        let spawns_fit = self
+            .rules
+            .weights
+            .iter()
+            .all(|(value, _)| BitBoard::can_merge(*value));
+        let tiles_fit = grid.tiles().all(|(_, value)| BitBoard::can_merge(value));
+        match BitBoard::try_from(grid) {
+            Ok(board) if spawns_fit && tiles_fit => Some(board),
+            _ => None,
+        }
+    }
+
+    /// The expected value of every move that changes the
+    /// board.
+    pub fn move_values<B: BoardModel>(&self, board: &B) -> Vec<(Direction, f64)>
+    where
+        E: Evaluate<B>,
+    {
+        self.moves(board, self.depth, &Budget::unlimited())
+    }
+
+    /// `move_values` searched one move deeper at a time,
+    /// up to `depth`, until `limit` runs out. Gives the
+    /// values from the deepest search that finished, and
+    /// how deep that was. When not even one move ahead
+    /// finishes, every move is valued by the board it
+    /// leaves before anything spawns, with a depth of 0.
+    pub fn move_values_within<B: BoardModel>(
+        &self,
+        board: &B,
+        limit: Duration,
+    ) -> (Vec<(Direction, f64)>, u32)
+    where
+        E: Evaluate<B>,
+    {
+        let deadline = Instant::now() + limit;
+        let mut deepest = None;
+        for depth in 1..=self.depth {
+            let budget = Budget::until(Some(deadline));
+            let values = self.moves(board, depth, &budget);
+            if budget.out_of_time.get() {
+                break;
+            }
+            deepest = Some((values, depth));
+            if Instant::now() >= deadline {
+                break;
+            }
+        }
+        deepest.unwrap_or_else(|| {
+            let values = Direction::ALL
+                .iter()
+                .filter_map(|&direction| {
+                    let (moved, _) = board.slide(direction)?;
+                    Some((direction, self.evaluation.evaluate(&moved)))
+                })
+                .collect();
+            (values, 0)
+        })
+    }
+
+    fn moves<B: BoardModel>(&self, board: &B, depth: u32, budget: &Budget) -> Vec<(Direction, f64)>
+    where
+        E: Evaluate<B>,
+    {
+        Direction::ALL
+            .iter()
+            .filter_map(|&direction| {
+                let (moved, _) = board.slide(direction)?;
+                let value = self.chance(moved, depth - 1, self.rules.tiles_per_turn, 1.0, budget);
+                Some((direction, value))
+            })
+            .collect()
+    }
+
+    // the best a player can expect from `grid`, with
+    // `depth` moves still to search
+    fn player<B: BoardModel>(&self, board: &B, depth: u32, probability: f64, budget: &Budget) -> f64
+    where
+        E: Evaluate<B>,
+    {
+        Direction::ALL
+            .iter()
+            .filter_map(|&direction| {
+                let (moved, _) = board.slide(direction)?;
+                Some(self.chance(
+                    moved,
+                    depth - 1,
+                    self.rules.tiles_per_turn,
+                    probability,
+                    budget,
+                ))
+            })
+            .fold(None, |best: Option<f64>, value| {
+                Some(best.map_or(value, |best| best.max(value)))
+            })
+            .unwrap_or(0.0)
+    }
+
+    // the average over every tile that could spawn next,
+    // weighted by how likely it is
+    fn chance<B: BoardModel>(
+        &self,
+        mut board: B,
+        depth: u32,
+        spawns: usize,
+        probability: f64,
+        budget: &Budget,
+    ) -> f64
+    where
+        E: Evaluate<B>,
+    {
+        // the whole search is thrown away, so any value will do
+        if budget.spent() {
+            return 0.0;
+        }
+        let empty = board.empty_positions();
+        if spawns == 0 || empty.is_empty() {
+            return match depth {
+                0 => self.evaluation.evaluate(&board),
+                _ => self.player(&board, depth, probability, budget),
+            };
+        }
+        if probability < self.min_probability {
+            return self.evaluation.evaluate(&board);
+        }
+
+        let total_weight: u32 = self.rules.weights.iter().map(|(_, weight)| weight).sum();
+        let mut expected = 0.0;
+        for &pos in &empty {
+            for &(value, weight) in &self.rules.weights {
+                if weight == 0 {
+                    continue;
+                }
+                let chance = f64::from(weight) / f64::from(total_weight) / empty.len() as f64;
+                board.set(pos, Some(value));
+                expected += chance
+                    * self.chance(
+                        board.clone(),
+                        depth,
+                        spawns - 1,
+                        probability * chance,
+                        budget,
+                    );
+            }
+            board.set(pos, None);
+        }
+        expected
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notation::BoardNotation;
+    use crate::options::MAX_AI_DEPTH;
+
+    fn grid(notation: &str) -> Grid {
+        notation.parse::<BoardNotation>().unwrap().grid
+    }
+
+    #[test]
+    fn takes_an_obvious_merge() {
+        // only the bottom row merges, and into the target
+        let grid = grid("4x4 2,4,2,4/4,2,4,2/2,4,2,4/1024,1024,8,.");
+        let solver = Expectimax::new(2, SpawnRules::default());
+        let values = solver.grid_move_values(&grid);
+        assert!(values
+            .iter()
+            .any(|(direction, _)| *direction == Direction::Down));
+        let (direction, _) = best(&values).unwrap();
+        assert!(matches!(direction, Direction::Left | Direction::Right));
+    }
+
+    #[test]
+    fn a_stuck_board_has_no_moves() {
+        let grid = grid("2x2 2,4/4,2");
+        let solver = Expectimax::new(2, SpawnRules::default());
+        assert_eq!(solver.best_grid_move(&grid), None);
+        assert!(solver.grid_move_values(&grid).is_empty());
+        let (values, _) = solver.grid_move_values_within(&grid, Duration::from_secs(1));
+        assert!(values.is_empty());
+    }
+
+    #[test]
+    fn searches_with_the_evaluation_it_is_given() {
+        // the more tiles in the top row, the better
+        let top_row = |grid: &Grid| {
+            (0..grid.width())
+                .filter(|&x| {
+                    grid.get(Position {
+                        x,
+                        y: grid.height() - 1,
+                    })
+                    .is_some()
+                })
+                .count() as f64
+        };
+        let grid = grid("4x4 .,.,.,./.,.,.,./.,.,.,./2,4,.,.");
+        let solver = Expectimax::with_evaluation(1, SpawnRules::default(), top_row);
+        // `Heuristic` would rather go right
+        let (direction, _) = solver.best_move(&grid).unwrap();
+        assert_eq!(direction, Direction::Up);
+    }
+
+    #[test]
+    fn no_time_values_the_boards_moves_leave() {
+        let grid = grid("4x4 .,.,.,./.,.,.,./.,.,.,./2,4,.,.");
+        let solver = Expectimax::new(MAX_AI_DEPTH, SpawnRules::default());
+        let (values, depth) = solver.move_values_within(&grid, Duration::ZERO);
+        assert_eq!(depth, 0);
+        assert_eq!(values.len(), 2);
+        for (direction, value) in values {
+            let moved = grid.shift(direction).grid;
+            assert_eq!(value, solver.evaluation.evaluate(&moved));
+        }
+    }
+
+    #[test]
+    fn searches_a_grid_when_its_tiles_wont_merge_on_a_bitboard() {
+        let solver = Expectimax::new(2, SpawnRules::default());
+        assert!(solver.as_bitboard(&grid("2x2 16384,16384/2,4")).is_some());
+
+        // two 32768s merge in the game, but not on a BitBoard
+        let grid = grid("2x2 32768,32768/2,4");
+        assert!(solver.as_bitboard(&grid).is_none());
+        let (direction, _) = solver.best_grid_move(&grid).unwrap();
+        assert!(matches!(direction, Direction::Left | Direction::Right));
+    }
+}
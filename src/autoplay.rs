@@ -0,0 +1,90 @@
+use crate::{pressed_direction, HistoryEvent, MoveEvent, REDO_KEY, UNDO_KEY};
+use bevy::prelude::*;
+use boxes::ai::{best, Expectimax};
+use boxes::components::{Block, Board, BoardConfig, Position};
+use boxes::grid::Grid;
+use boxes::options::Options;
+use boxes::spawn::SpawnRules;
+use std::time::Duration;
+
+pub const AUTOPLAY_KEY: KeyCode = KeyCode::A;
+const AI_DEPTH: u32 = 2;
+const AUTOPLAY_DELAY: Duration = Duration::from_millis(200);
+
+/// The computer playing by itself, one move every time the
+/// timer goes off.
+pub struct Autoplay {
+    pub enabled: bool,
+    timer: Timer,
+    solver: Expectimax,
+}
+
+impl Autoplay {
+    pub fn new(options: &Options, rules: SpawnRules, config: &BoardConfig) -> Self {
+        let delay = options
+            .auto_ms
+            .map_or(AUTOPLAY_DELAY, Duration::from_millis);
+        let solver = Expectimax::new(options.ai_depth.unwrap_or(AI_DEPTH), rules);
+        // built now rather than in the first move's time
+        solver.prepare(config.width, config.height);
+        Autoplay {
+            enabled: false,
+            timer: Timer::new(delay, true),
+            solver,
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.enabled = !self.enabled;
+        self.timer.reset();
+    }
+}
+
+pub fn autoplay(
+    keyboard_input: Res<Input<KeyCode>>,
+    time: Res<Time>,
+    mut history_reader: EventReader<HistoryEvent>,
+    mut autoplay: ResMut<Autoplay>,
+    query_board: Query<&Board>,
+    blocks: Query<(&Position, &Block)>,
+    mut move_writer: EventWriter<MoveEvent>,
+) {
+    if keyboard_input.just_pressed(AUTOPLAY_KEY) {
+        autoplay.toggle();
+    }
+    // the player takes over as soon as they move or undo
+    let took_over = pressed_direction(&keyboard_input).is_some()
+        || keyboard_input.just_pressed(UNDO_KEY)
+        || keyboard_input.just_pressed(REDO_KEY)
+        || history_reader.iter().next().is_some();
+    if took_over && autoplay.enabled {
+        autoplay.enabled = false;
+    }
+    if !autoplay.enabled {
+        return;
+    }
+    if !autoplay.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    let board = query_board.single().expect("expect there to be a board");
+    let grid = Grid::from_tiles(
+        board.width,
+        board.height,
+        blocks.iter().map(|(pos, block)| (*pos, block.value)),
+    );
+    // nothing to do unless a loaded game is another size
+    autoplay.solver.prepare(grid.width(), grid.height());
+    // the search holds up the frame, so it gets half the
+    // time between moves
+    let limit = autoplay.timer.duration() / 2;
+    let (values, _) = autoplay.solver.grid_move_values_within(&grid, limit);
+    if let Some((direction, _)) = best(&values) {
+        move_writer.send(MoveEvent(direction));
+    }
+}
+
+// a finished game is left for the player to look at
+pub fn stop_autoplay(mut autoplay: ResMut<Autoplay>) {
+    autoplay.enabled = false;
+}
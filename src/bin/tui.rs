@@ -0,0 +1,335 @@
+//! Plays the game in a terminal, for when there's no
+//! window to open, e.g. over SSH. The rules are the same
+//! ones the game window uses, and so are the board and
+//! rules options, see `--help`.
+
+use boxes::components::{Position, RunState, WinTarget};
+use boxes::grid::{Direction, Grid};
+use boxes::history::History;
+use boxes::options::Options;
+use boxes::presets::Presets;
+use boxes::rng::GameRng;
+use boxes::scores::{rules_key, BestScores};
+use boxes::snapshot::Snapshot;
+use boxes::spawn::SpawnRules;
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers};
+use crossterm::style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor};
+use crossterm::{cursor, execute, queue, terminal};
+use std::io::{self, Write};
+use std::process;
+
+const USAGE: &str = "\
+usage: tui [OPTIONS]
+
+Takes the board and rules options of the game window:
+--preset, --config, --size, --seed, --target, --undo-limit,
+--spawn, --tiles-per-turn and --starting-tiles. The
+window's other options aren't supported.
+
+Keys: arrows, wasd or hjkl to move, u to undo, n for a new
+game, c to keep going after winning, q to quit.
+";
+
+// every tile is drawn as a block this many characters
+// wide and lines tall
+const CELL_WIDTH: usize = 7;
+const CELL_HEIGHT: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Action {
+    Move(Direction),
+    Undo,
+    NewGame,
+    KeepGoing,
+    Quit,
+}
+
+fn action(key: KeyEvent) -> Option<Action> {
+    match key.code {
+        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => Some(Action::Quit),
+        KeyCode::Left | KeyCode::Char('a') | KeyCode::Char('h') => {
+            Some(Action::Move(Direction::Left))
+        }
+        KeyCode::Right | KeyCode::Char('d') | KeyCode::Char('l') => {
+            Some(Action::Move(Direction::Right))
+        }
+        KeyCode::Up | KeyCode::Char('w') | KeyCode::Char('k') => Some(Action::Move(Direction::Up)),
+        KeyCode::Down | KeyCode::Char('s') | KeyCode::Char('j') => {
+            Some(Action::Move(Direction::Down))
+        }
+        KeyCode::Char('u') => Some(Action::Undo),
+        KeyCode::Char('n') => Some(Action::NewGame),
+        KeyCode::Char('c') => Some(Action::KeepGoing),
+        KeyCode::Char('q') | KeyCode::Esc => Some(Action::Quit),
+        _ => None,
+    }
+}
+
+/// Everything the game window keeps in `Game` and its
+/// blocks, without the entities.
+struct TuiGame {
+    grid: Grid,
+    score: u32,
+    moves: u32,
+    endless: bool,
+    state: RunState,
+    spawn_rules: SpawnRules,
+    win_target: WinTarget,
+    game_rng: GameRng,
+    history: History,
+    best_scores: BestScores,
+    rules_key: String,
+}
+
+impl TuiGame {
+    fn new(options: &Options) -> Self {
+        let spawn_rules = options.spawn_rules();
+        let (width, height) = options.size.unwrap_or((4, 4));
+        let best_scores = BestScores::load().unwrap_or_default();
+        let mut game = TuiGame {
+            grid: Grid::new(width, height),
+            score: 0,
+            moves: 0,
+            endless: false,
+            state: RunState::Playing,
+            rules_key: rules_key(width, height, &spawn_rules),
+            spawn_rules,
+            win_target: options.target.map_or_else(WinTarget::default, WinTarget),
+            game_rng: options.seed.map_or_else(GameRng::default, GameRng::new),
+            history: History::new(options.undo_limit),
+            best_scores,
+        };
+        game.restart();
+        game
+    }
+
+    fn restart(&mut self) {
+        self.grid = Grid::new(self.grid.width(), self.grid.height());
+        self.spawn_rules.spawn(
+            &mut self.grid,
+            self.spawn_rules.starting_tiles,
+            &mut self.game_rng,
+        );
+        self.score = 0;
+        self.moves = 0;
+        self.endless = false;
+        self.state = RunState::Playing;
+        self.history.clear();
+    }
+
+    fn best(&self) -> u32 {
+        self.best_scores.get(&self.rules_key)
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            grid: self.grid.clone(),
+            score: self.score,
+            moves: self.moves,
+            rng: self.game_rng.state(),
+        }
+    }
+
+    fn restore(&mut self, snapshot: Snapshot) {
+        self.grid = snapshot.grid;
+        self.score = snapshot.score;
+        self.moves = snapshot.moves;
+        self.game_rng.restore(snapshot.rng);
+    }
+
+    fn shift(&mut self, direction: Direction) {
+        if self.state != RunState::Playing {
+            return;
+        }
+        let turn = match self.snapshot().play(direction, &self.spawn_rules) {
+            Some(turn) => turn,
+            None => return,
+        };
+        self.history.record(self.snapshot());
+        self.state = turn.state(&self.win_target, self.endless);
+        self.restore(turn.snapshot);
+        // a best score that can't be saved is still shown
+        let _ = self
+            .best_scores
+            .update_and_save(&self.rules_key, self.score);
+    }
+
+    // like the game window, only while playing
+    fn undo(&mut self) {
+        if self.state != RunState::Playing {
+            return;
+        }
+        if let Some(snapshot) = self.history.undo(self.snapshot()) {
+            self.restore(snapshot);
+        }
+    }
+
+    fn keep_going(&mut self) {
+        if self.state == RunState::Won {
+            self.endless = true;
+            self.state = RunState::Playing;
+        }
+    }
+}
+
+/// Raw mode on an alternate screen, put back the way it
+/// was when dropped, even when panicking.
+struct Screen;
+
+impl Screen {
+    fn enter() -> crossterm::Result<Self> {
+        terminal::enable_raw_mode()?;
+        execute!(io::stdout(), terminal::EnterAlternateScreen, cursor::Hide)?;
+        Ok(Screen)
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        let _ = execute!(io::stdout(), cursor::Show, terminal::LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+// background and text colours, every doubling gets its
+// own up to 2048
+fn tile_colors(value: Option<u32>) -> (Color, Color) {
+    let dark = Color::AnsiValue(236);
+    let light = Color::AnsiValue(231);
+    match value {
+        None => (Color::AnsiValue(250), Color::AnsiValue(250)),
+        Some(2) => (Color::AnsiValue(255), dark),
+        Some(4) => (Color::AnsiValue(230), dark),
+        Some(8) => (Color::AnsiValue(215), light),
+        Some(16) => (Color::AnsiValue(209), light),
+        Some(32) => (Color::AnsiValue(203), light),
+        Some(64) => (Color::AnsiValue(196), light),
+        Some(128) => (Color::AnsiValue(222), dark),
+        Some(256) => (Color::AnsiValue(221), dark),
+        Some(512) => (Color::AnsiValue(220), dark),
+        Some(1024) => (Color::AnsiValue(214), light),
+        Some(2048) => (Color::AnsiValue(208), light),
+        Some(_) => (Color::AnsiValue(235), light),
+    }
+}
+
+fn draw(out: &mut impl Write, game: &TuiGame) -> crossterm::Result<()> {
+    queue!(out, terminal::Clear(terminal::ClearType::All))?;
+    queue!(
+        out,
+        cursor::MoveTo(0, 0),
+        Print(format!(
+            " score {}   best {}   moves {}",
+            game.score,
+            game.best().max(game.score),
+            game.moves
+        ))
+    )?;
+    let mut line = 2;
+
+    let grid = &game.grid;
+    for y in (0..grid.height()).rev() {
+        for row in 0..CELL_HEIGHT {
+            queue!(out, cursor::MoveTo(1, line))?;
+            for x in 0..grid.width() {
+                let value = grid.get(Position { x, y });
+                let (background, foreground) = tile_colors(value);
+                let text = match value {
+                    Some(value) if row == CELL_HEIGHT / 2 => value.to_string(),
+                    _ => String::new(),
+                };
+                queue!(
+                    out,
+                    SetBackgroundColor(background),
+                    SetForegroundColor(foreground),
+                    Print(format!("{:^width$}", text, width = CELL_WIDTH)),
+                    ResetColor,
+                    Print(" ")
+                )?;
+            }
+            line += 1;
+        }
+        line += 1;
+    }
+
+    let status = match game.state {
+        RunState::Won => "You won! c to keep going, n for a new game",
+        RunState::GameOver => "Game over, n for a new game, q to quit",
+        _ => "arrows, wasd or hjkl to move, u to undo, n for a new game, q to quit",
+    };
+    queue!(out, cursor::MoveTo(0, line), Print(format!(" {}", status)))?;
+    out.flush()?;
+    Ok(())
+}
+
+fn main() {
+    let mut options = Options::parse(std::env::args().skip(1)).unwrap_or_else(|err| {
+        eprintln!("{}, see --help", err);
+        process::exit(2);
+    });
+    if options.help {
+        print!("{}", USAGE);
+        return;
+    }
+    if let Some(option) = unsupported_option(&options) {
+        eprintln!("tui doesn't support {}, see --help", option);
+        process::exit(2);
+    }
+    let presets = Presets::load(options.config.as_deref()).unwrap_or_else(|err| {
+        eprintln!("couldn't read presets: {}", err);
+        process::exit(2);
+    });
+    if let Err(err) = options.use_preset(&presets) {
+        eprintln!("{}", err);
+        process::exit(2);
+    }
+
+    let mut game = TuiGame::new(&options);
+    if let Err(err) = run(&mut game) {
+        eprintln!("terminal error: {}", err);
+        process::exit(1);
+    }
+    println!("score {}, best {}", game.score, game.best());
+}
+
+// options only the game window knows what to do with,
+// better refused than quietly ignored
+fn unsupported_option(options: &Options) -> Option<&'static str> {
+    let given = [
+        ("--list-presets", options.list_presets),
+        ("--continue", options.continue_game),
+        ("--position", options.position.is_some()),
+        ("--position-file", options.position_file.is_some()),
+        ("--replay", options.replay.is_some()),
+        ("--title", options.title.is_some()),
+        ("--animation-ms", options.animation_ms.is_some()),
+        ("--theme", options.theme.is_some()),
+        ("--ai-depth", options.ai_depth.is_some()),
+        ("--auto-ms", options.auto_ms.is_some()),
+        ("--headless", options.headless),
+    ];
+    given
+        .iter()
+        .find(|(_, given)| *given)
+        .map(|(option, _)| *option)
+}
+
+fn run(game: &mut TuiGame) -> crossterm::Result<()> {
+    let _screen = Screen::enter()?;
+    let mut out = io::stdout();
+    loop {
+        draw(&mut out, game)?;
+        let key = match event::read()? {
+            Event::Key(key) => key,
+            _ => continue,
+        };
+        match action(key) {
+            Some(Action::Move(direction)) => game.shift(direction),
+            Some(Action::Undo) => game.undo(),
+            Some(Action::NewGame) => game.restart(),
+            Some(Action::KeepGoing) => game.keep_going(),
+            Some(Action::Quit) => return Ok(()),
+            None => {}
+        }
+    }
+}
@@ -0,0 +1,143 @@
+//! Plays a replay back without a window and checks the
+//! result against what was claimed for it, either in the
+//! replay itself or with `--score` and `--max-tile`.
+//!
+//! The replay has to be played by the default rules, or
+//! the ones given with the rules options of the game,
+//! since anyone can edit the rules in a replay file.
+//!
+//! Exits with 1 if the claim doesn't hold or can't be
+//! checked, and 2 if the replay couldn't be read at all.
+
+use boxes::options::Options;
+use boxes::presets::Presets;
+use boxes::replay::{Replay, ReplayResult};
+use boxes::scores::rules_key;
+use std::path::Path;
+use std::process;
+
+const USAGE: &str = "\
+usage: verify_replay <replay.ron> [--score N] [--max-tile N] [RULES]
+
+RULES are the game's --preset, --config, --size, --spawn,
+--tiles-per-turn and --starting-tiles, the default rules
+are expected without them.";
+
+fn main() {
+    let mut path = None;
+    let mut claimed_score = None;
+    let mut claimed_max_tile = None;
+    // passed on to `Options`, which knows how to read them
+    let mut rules_args = vec![];
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--score" => claimed_score = Some(number_arg(&arg, args.next())),
+            "--max-tile" => claimed_max_tile = Some(number_arg(&arg, args.next())),
+            "--preset" | "--config" | "--size" | "--spawn" | "--tiles-per-turn"
+            | "--starting-tiles" => {
+                let value = args
+                    .next()
+                    .unwrap_or_else(|| fail_usage(&format!("{} needs a value", arg)));
+                rules_args.push(arg);
+                rules_args.push(value);
+            }
+            "--help" | "-h" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ if path.is_none() && !arg.starts_with("--") => path = Some(arg),
+            _ => fail_usage(&format!("unexpected argument {}", arg)),
+        }
+    }
+    let path = path.unwrap_or_else(|| fail_usage("missing the replay to verify"));
+    let mut options = Options::parse(rules_args).unwrap_or_else(|err| fail_usage(&err));
+    let presets = Presets::load(options.config.as_deref()).unwrap_or_else(|err| {
+        eprintln!("couldn't read presets: {}", err);
+        process::exit(2);
+    });
+    if let Err(err) = options.use_preset(&presets) {
+        fail_usage(&err);
+    }
+    let expected_rules = options.spawn_rules();
+
+    let replay = Replay::load(Path::new(&path)).unwrap_or_else(|err| {
+        eprintln!("couldn't read {}: {}", path, err);
+        process::exit(2);
+    });
+    println!(
+        "{}x{} board, seed {}, {} moves",
+        replay.width,
+        replay.height,
+        replay.seed,
+        replay.moves.len()
+    );
+    println!(
+        "rules {}",
+        rules_key(replay.width, replay.height, &replay.rules)
+    );
+    let last = match replay.snapshots() {
+        Ok(mut snapshots) => snapshots.pop().expect("there to be an initial snapshot"),
+        Err(err) => {
+            println!("invalid replay: {}", err);
+            process::exit(1);
+        }
+    };
+    let result = ReplayResult {
+        score: last.score,
+        max_tile: last.grid.max_tile().unwrap_or(0),
+    };
+    print!("{}", last.grid);
+    println!("score {}, max tile {}", result.score, result.max_tile);
+
+    // anything given on the command line beats what the
+    // replay says about itself
+    let recorded = replay.result;
+    let claimed = ReplayResult {
+        score: claimed_score
+            .or_else(|| recorded.map(|result| result.score))
+            .unwrap_or_else(|| fail_unverified("the replay doesn't claim a score")),
+        max_tile: claimed_max_tile
+            .or_else(|| recorded.map(|result| result.max_tile))
+            .unwrap_or(result.max_tile),
+    };
+    println!(
+        "claimed score {}, max tile {}",
+        claimed.score, claimed.max_tile
+    );
+    // any position could have been typed into a save file
+    if replay.start.is_some() {
+        fail_unverified("the replay starts from a loaded game, not a freshly dealt board");
+    }
+    let size_matches = options
+        .size
+        .map_or(true, |size| size == (replay.width, replay.height));
+    if replay.rules != expected_rules || !size_matches {
+        let (width, height) = options.size.unwrap_or((replay.width, replay.height));
+        fail_unverified(&format!(
+            "the replay wasn't played by the expected rules, {}",
+            rules_key(width, height, &expected_rules)
+        ));
+    }
+    if claimed != result {
+        println!("MISMATCH");
+        process::exit(1);
+    }
+    println!("OK");
+}
+
+fn number_arg(name: &str, value: Option<String>) -> u32 {
+    value
+        .and_then(|value| value.parse().ok())
+        .unwrap_or_else(|| fail_usage(&format!("{} needs a number", name)))
+}
+
+fn fail_usage(message: &str) -> ! {
+    eprintln!("{}\n{}", message, USAGE);
+    process::exit(2);
+}
+
+fn fail_unverified(reason: &str) -> ! {
+    println!("UNVERIFIED: {}", reason);
+    process::exit(1);
+}
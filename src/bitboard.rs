@@ -0,0 +1,392 @@
+use crate::components::{Position, MAX_BOARD_SIZE};
+use crate::grid::{Axis, BoardModel, Direction, Grid};
+use std::convert::TryFrom;
+use std::sync::OnceLock;
+
+/// Lines up to this long are moved with lookup tables,
+/// 16^5 entries is as big as they get.
+pub const MAX_TABLE_LENGTH: u8 = 5;
+// the biggest exponent that fits in a cell
+const MAX_RANK: u32 = 15;
+
+/// The same rules as `Grid`, packed for searching: every
+/// cell is the log2 of its tile in 4 bits, 0 for empty,
+/// so a 4x4 board is a single `u64` and an 8x8 one four.
+///
+/// Only tiles from 2 up to 32768 fit, and two 32768s don't
+/// merge, as the tile they'd make has no room.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BitBoard {
+    width: u8,
+    height: u8,
+    // cell `y * width + x` in the 4 bits from
+    // `(index % 16) * 4` of word `index / 16`
+    cells: [u64; 4],
+}
+
+/// A move on a `BitBoard`, like `MoveOutcome` without the
+/// tiles' paths.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BitOutcome {
+    pub board: BitBoard,
+    pub score: u32,
+    moved: bool,
+}
+
+impl BitOutcome {
+    pub fn moved(&self) -> bool {
+        self.moved
+    }
+}
+
+impl BitBoard {
+    pub fn new(width: u8, height: u8) -> Self {
+        assert!(
+            width <= MAX_BOARD_SIZE && height <= MAX_BOARD_SIZE,
+            "a {}x{} board doesn't fit in a BitBoard",
+            width,
+            height
+        );
+        BitBoard {
+            width,
+            height,
+            cells: [0; 4],
+        }
+    }
+
+    /// Whether a tile of `value` fits in a cell.
+    pub fn can_hold(value: u32) -> bool {
+        value.is_power_of_two() && (2..=1 << MAX_RANK).contains(&value)
+    }
+
+    /// Whether two tiles of `value` merge like they do on
+    /// a `Grid`, which is every tile that fits but 32768.
+    pub fn can_merge(value: u32) -> bool {
+        BitBoard::can_hold(value) && value < 1 << MAX_RANK
+    }
+
+    pub fn width(&self) -> u8 {
+        self.width
+    }
+
+    pub fn height(&self) -> u8 {
+        self.height
+    }
+
+    pub fn contains(&self, pos: Position) -> bool {
+        pos.x < self.width && pos.y < self.height
+    }
+
+    fn index(&self, pos: Position) -> usize {
+        assert!(
+            self.contains(pos),
+            "{:?} is outside of a {}x{} board",
+            pos,
+            self.width,
+            self.height
+        );
+        usize::from(pos.y) * usize::from(self.width) + usize::from(pos.x)
+    }
+
+    fn rank_at(&self, index: usize) -> u32 {
+        ((self.cells[index / 16] >> ((index % 16) * 4)) & 0xf) as u32
+    }
+
+    fn set_rank_at(&mut self, index: usize, rank: u32) {
+        let shift = (index % 16) * 4;
+        let word = &mut self.cells[index / 16];
+        *word = (*word & !(0xf << shift)) | (u64::from(rank) << shift);
+    }
+
+    pub fn get(&self, pos: Position) -> Option<u32> {
+        match self.rank_at(self.index(pos)) {
+            0 => None,
+            rank => Some(1 << rank),
+        }
+    }
+
+    /// Panics if `value` doesn't fit, see `can_hold`.
+    pub fn set(&mut self, pos: Position, value: Option<u32>) {
+        let rank = match value {
+            None => 0,
+            Some(value) => {
+                assert!(
+                    BitBoard::can_hold(value),
+                    "a BitBoard can't hold a tile of {}",
+                    value
+                );
+                value.trailing_zeros()
+            }
+        };
+        let index = self.index(pos);
+        self.set_rank_at(index, rank);
+    }
+
+    pub fn positions(&self) -> impl Iterator<Item = Position> {
+        let height = self.height;
+        (0..self.width).flat_map(move |x| (0..height).map(move |y| Position { x, y }))
+    }
+
+    pub fn tiles(&self) -> impl Iterator<Item = (Position, u32)> + '_ {
+        self.positions()
+            .filter_map(move |pos| self.get(pos).map(|value| (pos, value)))
+    }
+
+    pub fn max_tile(&self) -> Option<u32> {
+        self.tiles().map(|(_, value)| value).max()
+    }
+
+    pub fn empty_positions(&self) -> impl Iterator<Item = Position> + '_ {
+        let width = usize::from(self.width);
+        self.positions()
+            .filter(move |pos| self.rank_at(usize::from(pos.y) * width + usize::from(pos.x)) == 0)
+    }
+
+    pub fn has_legal_move(&self) -> bool {
+        // once the board is full only merges move tiles,
+        // and those work both ways along a line
+        self.empty_positions().next().is_some()
+            || [Direction::Left, Direction::Down]
+                .iter()
+                .any(|direction| self.shift(*direction).moved())
+    }
+
+    /// Every row from the bottom up, packed with the log2
+    /// of the tile at `x` 0 in the lowest 4 bits.
+    pub fn rows(&self) -> impl Iterator<Item = u32> + '_ {
+        (0..self.height).map(move |y| self.row(y))
+    }
+
+    /// Every column from the left, packed like `rows` with
+    /// `y` 0 in the lowest 4 bits.
+    pub fn columns(&self) -> impl Iterator<Item = u32> {
+        let transposed = self.transpose();
+        (0..transposed.height).map(move |x| transposed.row(x))
+    }
+
+    // read through a window that spans the word the row
+    // starts in and the next one
+    fn row(&self, y: u8) -> u32 {
+        let bit = usize::from(y) * usize::from(self.width) * 4;
+        let (word, offset) = (bit / 64, bit % 64);
+        let mut window = u128::from(self.cells[word]);
+        if let Some(next) = self.cells.get(word + 1) {
+            window |= u128::from(*next) << 64;
+        }
+        (window >> offset) as u32 & line_mask(self.width)
+    }
+
+    fn set_row(&mut self, y: u8, row: u32) {
+        let bit = usize::from(y) * usize::from(self.width) * 4;
+        let (word, offset) = (bit / 64, bit % 64);
+        let mask = u128::from(line_mask(self.width)) << offset;
+        let row = u128::from(row) << offset;
+        self.cells[word] = (self.cells[word] & !mask as u64) | row as u64;
+        if let Some(next) = self.cells.get_mut(word + 1) {
+            *next = (*next & !(mask >> 64) as u64) | (row >> 64) as u64;
+        }
+    }
+
+    // swaps rows and columns, so columns can be moved with
+    // the same tables as rows
+    fn transpose(&self) -> BitBoard {
+        let mut transposed = BitBoard::new(self.height, self.width);
+        let (width, height) = (usize::from(self.width), usize::from(self.height));
+        for y in 0..height {
+            for x in 0..width {
+                transposed.set_rank_at(x * height + y, self.rank_at(y * width + x));
+            }
+        }
+        transposed
+    }
+
+    /// Works out the tables a board this size moves with
+    /// now, rather than on its first move.
+    pub fn prepare(width: u8, height: u8) {
+        line_table(width);
+        line_table(height);
+    }
+
+    pub fn shift(&self, direction: Direction) -> BitOutcome {
+        let (mut board, towards_end) = match direction {
+            Direction::Left => (*self, false),
+            Direction::Right => (*self, true),
+            Direction::Down => (self.transpose(), false),
+            Direction::Up => (self.transpose(), true),
+        };
+        let mut score = 0;
+        let mut moved = false;
+        for y in 0..board.height {
+            let row = board.row(y);
+            if row == 0 {
+                continue;
+            }
+            let (shifted, row_score) = move_line(row, board.width, towards_end);
+            if shifted != row {
+                board.set_row(y, shifted);
+                score += row_score;
+                moved = true;
+            }
+        }
+        if direction.axis() == Axis::Y {
+            board = board.transpose();
+        }
+        BitOutcome {
+            board,
+            score,
+            moved,
+        }
+    }
+}
+
+fn line_mask(length: u8) -> u32 {
+    u32::MAX >> (32 - 4 * u32::from(length))
+}
+
+// the same line with the cells in the opposite order
+fn reverse_line(ranks: u32, length: u8) -> u32 {
+    (0..length).fold(0, |reversed, cell| {
+        reversed | ((ranks >> (cell * 4)) & 0xf) << ((length - 1 - cell) * 4)
+    })
+}
+
+// every line of a length, packed like a row of a board,
+// after moving towards either end and the score that
+// earned, which is the same both ways
+struct LineTable {
+    towards_start: Vec<u32>,
+    towards_end: Vec<u32>,
+    score: Vec<u32>,
+}
+
+static LINE_TABLES: [OnceLock<LineTable>; (MAX_TABLE_LENGTH - 1) as usize] = [
+    OnceLock::new(),
+    OnceLock::new(),
+    OnceLock::new(),
+    OnceLock::new(),
+];
+
+fn line_table(length: u8) -> Option<&'static LineTable> {
+    if !(2..=MAX_TABLE_LENGTH).contains(&length) {
+        return None;
+    }
+    Some(LINE_TABLES[usize::from(length - 2)].get_or_init(|| {
+        let lines = 0..1 << (4 * u32::from(length));
+        let (towards_start, score) = lines
+            .clone()
+            .map(|ranks| compute_line(ranks, length, false))
+            .unzip();
+        let towards_end = lines
+            .map(|ranks| compute_line(ranks, length, true).0)
+            .collect();
+        LineTable {
+            towards_start,
+            towards_end,
+            score,
+        }
+    }))
+}
+
+fn move_line(ranks: u32, length: u8, towards_end: bool) -> (u32, u32) {
+    let table = match line_table(length) {
+        Some(table) => table,
+        None => return compute_line(ranks, length, towards_end),
+    };
+    let index = ranks as usize;
+    match towards_end {
+        false => (table.towards_start[index], table.score[index]),
+        true => (table.towards_end[index], table.score[index]),
+    }
+}
+
+// the same as a line of `Grid::shift`
+fn compute_line(ranks: u32, length: u8, towards_end: bool) -> (u32, u32) {
+    if towards_end {
+        let (moved, score) = compute_line(reverse_line(ranks, length), length, false);
+        return (reverse_line(moved, length), score);
+    }
+    let mut moved = 0;
+    let mut score = 0;
+    let mut target = 0;
+    let mut pending = None;
+    for step in 0..length {
+        let rank = (ranks >> (step * 4)) & 0xf;
+        if rank == 0 {
+            continue;
+        }
+        match pending {
+            Some(pending_rank) if pending_rank == rank && rank < MAX_RANK => {
+                moved |= (rank + 1) << (target * 4);
+                score += 1 << (rank + 1);
+                target += 1;
+                pending = None;
+            }
+            Some(pending_rank) => {
+                moved |= pending_rank << (target * 4);
+                target += 1;
+                pending = Some(rank);
+            }
+            None => pending = Some(rank),
+        }
+    }
+    if let Some(pending_rank) = pending {
+        moved |= pending_rank << (target * 4);
+    }
+    (moved, score)
+}
+
+impl TryFrom<&Grid> for BitBoard {
+    type Error = String;
+
+    fn try_from(grid: &Grid) -> Result<Self, Self::Error> {
+        let mut board = BitBoard::new(grid.width(), grid.height());
+        for (pos, value) in grid.tiles() {
+            if !BitBoard::can_hold(value) {
+                return Err(format!("a BitBoard can't hold a tile of {}", value));
+            }
+            board.set(pos, Some(value));
+        }
+        Ok(board)
+    }
+}
+
+impl From<&BitBoard> for Grid {
+    fn from(board: &BitBoard) -> Self {
+        Grid::from_tiles(board.width, board.height, board.tiles())
+    }
+}
+
+impl BoardModel for BitBoard {
+    fn width(&self) -> u8 {
+        self.width
+    }
+
+    fn height(&self) -> u8 {
+        self.height
+    }
+
+    fn get(&self, pos: Position) -> Option<u32> {
+        BitBoard::get(self, pos)
+    }
+
+    fn set(&mut self, pos: Position, value: Option<u32>) {
+        BitBoard::set(self, pos, value)
+    }
+
+    fn empty_positions(&self) -> Vec<Position> {
+        BitBoard::empty_positions(self).collect()
+    }
+
+    fn has_legal_move(&self) -> bool {
+        BitBoard::has_legal_move(self)
+    }
+
+    fn slide(&self, direction: Direction) -> Option<(Self, u32)> {
+        let outcome = self.shift(direction);
+        if outcome.moved() {
+            Some((outcome.board, outcome.score))
+        } else {
+            None
+        }
+    }
+}
@@ -1,4 +1,7 @@
-#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+use crate::grid::MoveOutcome;
+use serde::{Deserialize, Serialize};
+
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct Position {
     pub x: u8,
     pub y: u8,
@@ -10,17 +13,82 @@ pub struct Block {
 pub struct BlockText;
 
 pub struct Board {
-    pub size: u8,
+    pub width: u8,
+    pub height: u8,
+}
+
+pub const MIN_BOARD_SIZE: u8 = 2;
+pub const MAX_BOARD_SIZE: u8 = 8;
+
+// the board the game should be played on,
+// the `Board` component is spawned from this
+pub struct BoardConfig {
+    pub width: u8,
+    pub height: u8,
+}
+
+impl BoardConfig {
+    pub fn new(width: u8, height: u8) -> Self {
+        for (name, length) in [("width", width), ("height", height)].iter() {
+            assert!(
+                (MIN_BOARD_SIZE..=MAX_BOARD_SIZE).contains(length),
+                "board {} must be between {} and {}, got {}",
+                name,
+                MIN_BOARD_SIZE,
+                MAX_BOARD_SIZE,
+                length
+            );
+        }
+        BoardConfig { width, height }
+    }
+}
+
+impl Default for BoardConfig {
+    fn default() -> Self {
+        BoardConfig {
+            width: 4,
+            height: 4,
+        }
+    }
 }
 
 #[derive(Default)]
 pub struct Game {
     pub score: u32,
     pub score_best: u32,
+    pub moves: u32,
+    // the player already won and chose to keep going
+    pub endless: bool,
+}
+
+// reaching a tile of this value wins the game
+pub struct WinTarget(pub u32);
+
+impl Default for WinTarget {
+    fn default() -> Self {
+        WinTarget(2048)
+    }
+}
+
+impl WinTarget {
+    /// Whether a move merged a tile big enough to win. A
+    /// spawned tile, or one that was already on the board,
+    /// doesn't count.
+    pub fn reached_by(&self, outcome: &MoveOutcome) -> bool {
+        outcome.merges.iter().any(|merge| merge.value >= self.0)
+    }
 }
 
-#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub enum RunState {
     Playing,
     GameOver,
+    // pushed on top of Playing, so the game underneath
+    // can be resumed
+    Won,
+    // the save slot panel, pushed on top of whatever
+    // state the game was in
+    Slots,
+    // watching a replay instead of playing
+    Replay,
 }
@@ -0,0 +1,521 @@
+use crate::components::{Position, MAX_BOARD_SIZE, MIN_BOARD_SIZE};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Axis {
+    X,
+    Y,
+}
+
+impl Direction {
+    pub const ALL: [Direction; 4] = [
+        Direction::Left,
+        Direction::Right,
+        Direction::Up,
+        Direction::Down,
+    ];
+
+    /// The axis tiles travel along.
+    pub fn axis(self) -> Axis {
+        match self {
+            Direction::Left | Direction::Right => Axis::X,
+            Direction::Up | Direction::Down => Axis::Y,
+        }
+    }
+
+    /// Whether tiles are pushed towards the high end of
+    /// their axis (`width - 1` or `height - 1`) rather than towards 0.
+    pub fn is_reversed(self) -> bool {
+        match self {
+            Direction::Right | Direction::Up => true,
+            Direction::Left | Direction::Down => false,
+        }
+    }
+    /// The letter a direction is written as in replays.
+    pub fn letter(self) -> char {
+        match self {
+            Direction::Left => 'L',
+            Direction::Right => 'R',
+            Direction::Up => 'U',
+            Direction::Down => 'D',
+        }
+    }
+
+    pub fn from_letter(letter: char) -> Option<Direction> {
+        Direction::ALL
+            .iter()
+            .copied()
+            .find(|direction| direction.letter() == letter)
+    }
+}
+
+/// What spawning tiles and searching for moves need from a
+/// board, so they work the same on a `Grid` and on a
+/// `BitBoard`.
+pub trait BoardModel: Clone {
+    fn width(&self) -> u8;
+    fn height(&self) -> u8;
+    fn get(&self, pos: Position) -> Option<u32>;
+    fn set(&mut self, pos: Position, value: Option<u32>);
+    /// In the same order as `Grid::positions`, so the same
+    /// rng spawns the same tiles on either board.
+    fn empty_positions(&self) -> Vec<Position>;
+    fn has_legal_move(&self) -> bool;
+    /// The board after a move and the score it earned, or
+    /// `None` if the move doesn't change anything.
+    fn slide(&self, direction: Direction) -> Option<(Self, u32)>;
+}
+
+/// The 2048 rules without any of the Bevy machinery.
+///
+/// Cells are stored row by row starting at the bottom
+/// left, which matches how `Position` is laid out on
+/// screen (y grows upwards).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "SavedGrid", into = "SavedGrid")]
+pub struct Grid {
+    width: u8,
+    height: u8,
+    cells: Vec<Option<u32>>,
+}
+
+// how a grid is written to disk: a list of tiles is
+// easier to read than a wall of empty cells
+#[derive(Serialize, Deserialize)]
+struct SavedGrid {
+    width: u8,
+    height: u8,
+    tiles: Vec<(Position, u32)>,
+}
+
+impl From<Grid> for SavedGrid {
+    fn from(grid: Grid) -> Self {
+        SavedGrid {
+            width: grid.width,
+            height: grid.height,
+            tiles: grid.tiles().collect(),
+        }
+    }
+}
+
+impl TryFrom<SavedGrid> for Grid {
+    type Error = String;
+
+    fn try_from(saved: SavedGrid) -> Result<Self, Self::Error> {
+        let sizes = MIN_BOARD_SIZE..=MAX_BOARD_SIZE;
+        if !sizes.contains(&saved.width) || !sizes.contains(&saved.height) {
+            return Err(format!(
+                "board sides must be between {} and {}, got a {}x{} grid",
+                MIN_BOARD_SIZE, MAX_BOARD_SIZE, saved.width, saved.height
+            ));
+        }
+        let mut grid = Grid::new(saved.width, saved.height);
+        for (pos, value) in saved.tiles {
+            if !grid.contains(pos) {
+                return Err(format!(
+                    "tile at {:?} is outside of a {}x{} grid",
+                    pos, saved.width, saved.height
+                ));
+            }
+            grid.set(pos, Some(value));
+        }
+        Ok(grid)
+    }
+}
+
+/// A tile that survived the move but ended up somewhere
+/// else.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Slide {
+    pub from: Position,
+    pub to: Position,
+}
+
+/// Two tiles that combined into one. `kept` and
+/// `absorbed` are where the tiles started, `to` is where
+/// the combined tile ends up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Merge {
+    pub kept: Position,
+    pub absorbed: Position,
+    pub to: Position,
+    pub value: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MoveOutcome {
+    pub grid: Grid,
+    pub slides: Vec<Slide>,
+    pub merges: Vec<Merge>,
+    pub score: u32,
+}
+
+impl Grid {
+    pub fn new(width: u8, height: u8) -> Self {
+        Grid {
+            width,
+            height,
+            cells: vec![None; usize::from(width) * usize::from(height)],
+        }
+    }
+
+    pub fn from_tiles(
+        width: u8,
+        height: u8,
+        tiles: impl IntoIterator<Item = (Position, u32)>,
+    ) -> Self {
+        let mut grid = Grid::new(width, height);
+        for (pos, value) in tiles {
+            grid.set(pos, Some(value));
+        }
+        grid
+    }
+
+    pub fn width(&self) -> u8 {
+        self.width
+    }
+
+    pub fn height(&self) -> u8 {
+        self.height
+    }
+
+    pub fn contains(&self, pos: Position) -> bool {
+        pos.x < self.width && pos.y < self.height
+    }
+
+    fn index(&self, pos: Position) -> usize {
+        assert!(
+            self.contains(pos),
+            "{:?} is outside of a {}x{} grid",
+            pos,
+            self.width,
+            self.height
+        );
+        usize::from(pos.y) * usize::from(self.width) + usize::from(pos.x)
+    }
+
+    pub fn get(&self, pos: Position) -> Option<u32> {
+        self.cells[self.index(pos)]
+    }
+
+    pub fn set(&mut self, pos: Position, value: Option<u32>) {
+        let index = self.index(pos);
+        self.cells[index] = value;
+    }
+
+    pub fn positions(&self) -> impl Iterator<Item = Position> {
+        (0..self.width)
+            .cartesian_product(0..self.height)
+            .map(|(x, y)| Position { x, y })
+    }
+
+    pub fn tiles(&self) -> impl Iterator<Item = (Position, u32)> + '_ {
+        self.positions()
+            .filter_map(move |pos| self.get(pos).map(|value| (pos, value)))
+    }
+
+    pub fn max_tile(&self) -> Option<u32> {
+        self.cells.iter().flatten().copied().max()
+    }
+
+    pub fn empty_positions(&self) -> impl Iterator<Item = Position> + '_ {
+        self.positions().filter(move |pos| self.get(*pos).is_none())
+    }
+
+    /// Whether any direction would change the grid: there
+    /// is an empty cell, or two equal tiles sit next to
+    /// each other.
+    pub fn has_legal_move(&self) -> bool {
+        self.positions().any(|pos| match self.get(pos) {
+            None => true,
+            Some(value) => {
+                // checking right and up covers every pair
+                // of neighbours exactly once
+                let right = Position {
+                    x: pos.x + 1,
+                    ..pos
+                };
+                let up = Position {
+                    y: pos.y + 1,
+                    ..pos
+                };
+                [right, up]
+                    .iter()
+                    .any(|next| self.contains(*next) && self.get(*next) == Some(value))
+            }
+        })
+    }
+
+    // every row (or column) the tiles travel along,
+    // ordered from the edge they are pushed towards
+    fn lines(&self, direction: Direction) -> Vec<Vec<Position>> {
+        let (length, count) = match direction.axis() {
+            Axis::X => (self.width, self.height),
+            Axis::Y => (self.height, self.width),
+        };
+        (0..count)
+            .map(|line| {
+                let mut cells: Vec<Position> = (0..length)
+                    .map(|step| match direction.axis() {
+                        Axis::X => Position { x: step, y: line },
+                        Axis::Y => Position { x: line, y: step },
+                    })
+                    .collect();
+                if direction.is_reversed() {
+                    cells.reverse();
+                }
+                cells
+            })
+            .collect()
+    }
+
+    pub fn shift(&self, direction: Direction) -> MoveOutcome {
+        let mut outcome = MoveOutcome {
+            grid: Grid::new(self.width, self.height),
+            slides: vec![],
+            merges: vec![],
+            score: 0,
+        };
+
+        for line in self.lines(direction) {
+            let mut target = line.iter();
+            // the last tile we saw that hasn't been placed
+            // yet, because the next one might merge into it
+            let mut pending: Option<(Position, u32)> = None;
+
+            for &pos in line.iter() {
+                let value = match self.get(pos) {
+                    Some(value) => value,
+                    None => continue,
+                };
+                match pending {
+                    Some((kept, pending_value)) if pending_value == value => {
+                        let to = *target.next().expect("a merge always has room");
+                        let merged = pending_value + value;
+                        outcome.grid.set(to, Some(merged));
+                        outcome.merges.push(Merge {
+                            kept,
+                            absorbed: pos,
+                            to,
+                            value: merged,
+                        });
+                        outcome.score += merged;
+                        pending = None;
+                    }
+                    Some(previous) => {
+                        outcome.place(previous, *target.next().expect("a tile always has room"));
+                        pending = Some((pos, value));
+                    }
+                    None => {
+                        pending = Some((pos, value));
+                    }
+                }
+            }
+            if let Some(previous) = pending {
+                outcome.place(previous, *target.next().expect("a tile always has room"));
+            }
+        }
+
+        outcome
+    }
+}
+
+impl BoardModel for Grid {
+    fn width(&self) -> u8 {
+        self.width
+    }
+
+    fn height(&self) -> u8 {
+        self.height
+    }
+
+    fn get(&self, pos: Position) -> Option<u32> {
+        Grid::get(self, pos)
+    }
+
+    fn set(&mut self, pos: Position, value: Option<u32>) {
+        Grid::set(self, pos, value)
+    }
+
+    fn empty_positions(&self) -> Vec<Position> {
+        Grid::empty_positions(self).collect()
+    }
+
+    fn has_legal_move(&self) -> bool {
+        Grid::has_legal_move(self)
+    }
+
+    fn slide(&self, direction: Direction) -> Option<(Self, u32)> {
+        let outcome = self.shift(direction);
+        if outcome.moved() {
+            Some((outcome.grid, outcome.score))
+        } else {
+            None
+        }
+    }
+}
+
+// the board as it looks on screen, top row first and
+// `.` for empty cells
+impl fmt::Display for Grid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let cell_width = self.max_tile().map_or(1, |value| value.to_string().len());
+        for y in (0..self.height).rev() {
+            let row = (0..self.width)
+                .map(|x| match self.get(Position { x, y }) {
+                    Some(value) => format!("{:>width$}", value, width = cell_width),
+                    None => format!("{:>width$}", ".", width = cell_width),
+                })
+                .join(" ");
+            writeln!(f, "{}", row)?;
+        }
+        Ok(())
+    }
+}
+
+impl MoveOutcome {
+    /// Whether any tile changed position or value. A move
+    /// that does nothing shouldn't count as a turn.
+    pub fn moved(&self) -> bool {
+        !self.slides.is_empty() || !self.merges.is_empty()
+    }
+
+    fn place(&mut self, (from, value): (Position, u32), to: Position) {
+        self.grid.set(to, Some(value));
+        if from != to {
+            self.slides.push(Slide { from, to });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a grid from its rows, top row first like `Display`,
+    // 0 for an empty cell
+    fn grid(rows: &[&[u32]]) -> Grid {
+        let height = rows.len() as u8;
+        let width = rows[0].len() as u8;
+        let mut grid = Grid::new(width, height);
+        for (row, values) in rows.iter().enumerate() {
+            for (x, &value) in values.iter().enumerate() {
+                let pos = Position {
+                    x: x as u8,
+                    y: height - 1 - row as u8,
+                };
+                grid.set(pos, Some(value).filter(|&value| value != 0));
+            }
+        }
+        grid
+    }
+
+    #[test]
+    fn tiles_merge_at_most_once_per_move() {
+        let outcome = grid(&[&[2, 2, 2, 2]]).shift(Direction::Left);
+        assert_eq!(outcome.grid, grid(&[&[4, 4, 0, 0]]));
+        assert_eq!(outcome.score, 8);
+        assert_eq!(outcome.merges.len(), 2);
+
+        let outcome = grid(&[&[2, 2, 4]]).shift(Direction::Left);
+        assert_eq!(outcome.grid, grid(&[&[4, 4, 0]]));
+        assert_eq!(outcome.score, 4);
+    }
+
+    #[test]
+    fn right_and_up_merge_from_the_far_end() {
+        let outcome = grid(&[&[2, 2, 2, 0]]).shift(Direction::Right);
+        assert_eq!(outcome.grid, grid(&[&[0, 0, 2, 4]]));
+
+        let outcome = grid(&[&[0], &[2], &[2], &[2]]).shift(Direction::Up);
+        assert_eq!(outcome.grid, grid(&[&[4], &[2], &[0], &[0]]));
+
+        let outcome = grid(&[&[2], &[2], &[2], &[0]]).shift(Direction::Down);
+        assert_eq!(outcome.grid, grid(&[&[0], &[0], &[2], &[4]]));
+    }
+
+    #[test]
+    fn a_move_that_changes_nothing_didnt_move() {
+        let board = grid(&[&[2, 4, 0], &[8, 0, 0]]);
+        let outcome = board.shift(Direction::Left);
+        assert!(!outcome.moved());
+        assert_eq!(outcome.grid, board);
+        assert_eq!(outcome.score, 0);
+
+        assert!(board.shift(Direction::Right).moved());
+    }
+
+    #[test]
+    fn merges_and_slides_say_where_tiles_went() {
+        let at = |x, y| Position { x, y };
+        let outcome = grid(&[&[0, 2, 2, 8]]).shift(Direction::Left);
+        assert_eq!(
+            outcome.merges,
+            vec![Merge {
+                kept: at(1, 0),
+                absorbed: at(2, 0),
+                to: at(0, 0),
+                value: 4,
+            }]
+        );
+        assert_eq!(
+            outcome.slides,
+            vec![Slide {
+                from: at(3, 0),
+                to: at(1, 0),
+            }]
+        );
+
+        // moving right, the tile nearest the right edge is kept
+        let outcome = grid(&[&[2, 2, 0]]).shift(Direction::Right);
+        assert_eq!(
+            outcome.merges,
+            vec![Merge {
+                kept: at(1, 0),
+                absorbed: at(0, 0),
+                to: at(2, 0),
+                value: 4,
+            }]
+        );
+        assert!(outcome.slides.is_empty());
+    }
+
+    #[test]
+    fn full_boards_need_equal_neighbours_to_move() {
+        let stuck = grid(&[&[2, 4, 2], &[4, 2, 4]]);
+        assert!(!stuck.has_legal_move());
+        assert!(Direction::ALL
+            .iter()
+            .all(|&direction| !stuck.shift(direction).moved()));
+
+        // a pair along a row
+        assert!(grid(&[&[2, 2, 4], &[4, 8, 2]]).has_legal_move());
+        // a pair along a column, in the last column
+        assert!(grid(&[&[2, 4, 8], &[4, 2, 8]]).has_legal_move());
+        assert!(grid(&[&[2, 4], &[4, 0]]).has_legal_move());
+    }
+
+    #[test]
+    fn saved_grids_round_trip_and_are_checked() {
+        let board = grid(&[&[2, 0, 4], &[0, 8, 0]]);
+        let saved = ron::to_string(&board).unwrap();
+        assert_eq!(ron::from_str::<Grid>(&saved).unwrap(), board);
+
+        assert!(ron::from_str::<Grid>("(width: 12, height: 1, tiles: [])").is_err());
+        assert!(ron::from_str::<Grid>("(width: 4, height: 0, tiles: [])").is_err());
+        assert!(
+            ron::from_str::<Grid>("(width: 2, height: 2, tiles: [((x: 2, y: 0), 2)])").is_err()
+        );
+    }
+}
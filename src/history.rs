@@ -0,0 +1,129 @@
+use crate::snapshot::Snapshot;
+use std::collections::VecDeque;
+
+/// Undo and redo stacks of whole-board snapshots.
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    undo: VecDeque<Snapshot>,
+    redo: Vec<Snapshot>,
+    // how many moves can be undone, `None` for no limit
+    // and `Some(0)` to turn undo off
+    limit: Option<usize>,
+}
+
+impl History {
+    pub fn new(limit: Option<usize>) -> Self {
+        History {
+            limit,
+            ..Default::default()
+        }
+    }
+
+    pub fn limit(&self) -> Option<usize> {
+        self.limit
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// Remember the state from before a move. Making a
+    /// new move throws away anything that could be redone.
+    pub fn record(&mut self, before: Snapshot) {
+        self.redo.clear();
+        if self.limit == Some(0) {
+            return;
+        }
+        self.undo.push_back(before);
+        if let Some(limit) = self.limit {
+            while self.undo.len() > limit {
+                self.undo.pop_front();
+            }
+        }
+    }
+
+    /// Step back one move. `current` is kept so the move
+    /// can be redone.
+    pub fn undo(&mut self, current: Snapshot) -> Option<Snapshot> {
+        let previous = self.undo.pop_back()?;
+        self.redo.push(current);
+        Some(previous)
+    }
+
+    pub fn redo(&mut self, current: Snapshot) -> Option<Snapshot> {
+        let next = self.redo.pop()?;
+        self.undo.push_back(current);
+        Some(next)
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::Grid;
+    use crate::rng::GameRng;
+
+    // snapshots told apart by their move count
+    fn snapshot(moves: u32) -> Snapshot {
+        Snapshot {
+            grid: Grid::new(4, 4),
+            score: 0,
+            moves,
+            rng: GameRng::new(1).state(),
+        }
+    }
+
+    #[test]
+    fn undo_then_redo_steps_back_and_forth() {
+        let mut history = History::new(None);
+        history.record(snapshot(0));
+        history.record(snapshot(1));
+        assert_eq!(history.undo(snapshot(2)), Some(snapshot(1)));
+        assert_eq!(history.undo(snapshot(1)), Some(snapshot(0)));
+        assert_eq!(history.undo(snapshot(0)), None);
+        assert_eq!(history.redo(snapshot(0)), Some(snapshot(1)));
+        assert_eq!(history.redo(snapshot(1)), Some(snapshot(2)));
+        assert_eq!(history.redo(snapshot(2)), None);
+        assert_eq!(history.undo(snapshot(2)), Some(snapshot(1)));
+    }
+
+    #[test]
+    fn a_new_move_clears_redo() {
+        let mut history = History::new(None);
+        history.record(snapshot(0));
+        history.undo(snapshot(1));
+        assert!(history.can_redo());
+        history.record(snapshot(0));
+        assert!(!history.can_redo());
+        assert_eq!(history.redo(snapshot(1)), None);
+    }
+
+    #[test]
+    fn only_the_latest_moves_are_kept() {
+        let mut history = History::new(Some(2));
+        for moves in 0..5 {
+            history.record(snapshot(moves));
+        }
+        assert_eq!(history.undo(snapshot(5)), Some(snapshot(4)));
+        assert_eq!(history.undo(snapshot(4)), Some(snapshot(3)));
+        assert_eq!(history.undo(snapshot(3)), None);
+    }
+
+    #[test]
+    fn a_limit_of_zero_turns_undo_off() {
+        let mut history = History::new(Some(0));
+        history.record(snapshot(0));
+        assert!(!history.can_undo());
+        assert_eq!(history.undo(snapshot(1)), None);
+        assert!(!history.can_redo());
+    }
+}
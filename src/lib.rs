@@ -0,0 +1,16 @@
+pub mod ai;
+pub mod bitboard;
+pub mod components;
+pub mod grid;
+pub mod history;
+pub mod notation;
+pub mod options;
+pub mod presets;
+pub mod replay;
+pub mod rng;
+pub mod save;
+pub mod scores;
+pub mod slots;
+pub mod snapshot;
+pub mod spawn;
+pub mod storage;
@@ -1,21 +1,140 @@
+use bevy::app::AppExit;
 use bevy::prelude::*;
 use bevy_easings::*;
 use itertools::Itertools;
-use rand::prelude::*;
 use std::collections::HashMap;
-use std::convert::TryInto;
-use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-mod components;
+mod autoplay;
+mod playback;
 mod ui;
 
-use components::*;
+use autoplay::*;
+use boxes::components::*;
+use boxes::grid::{Direction, Grid, MoveOutcome};
+use boxes::history::History;
+use boxes::notation::BoardNotation;
+use boxes::options::{Options, Theme, HELP};
+use boxes::presets::Presets;
+use boxes::replay::{Replay, ReplayResult};
+use boxes::rng::GameRng;
+use boxes::save::SavedGame;
+use boxes::scores::{rules_key, BestScores};
+use boxes::snapshot::Snapshot;
+use boxes::spawn::SpawnRules;
+use playback::*;
 use ui::*;
 
 const TILE_SPACER: f32 = 10.0;
 const TILE_SIZE: f32 = 40.0;
+const ANIMATION_MS: u64 = 100;
+
+const KEY_BINDINGS: [(KeyCode, Direction); 4] = [
+    (KeyCode::Left, Direction::Left),
+    (KeyCode::Right, Direction::Right),
+    (KeyCode::Down, Direction::Down),
+    (KeyCode::Up, Direction::Up),
+];
+const UNDO_KEY: KeyCode = KeyCode::Z;
+const REDO_KEY: KeyCode = KeyCode::Y;
+
+pub struct GameOverEvent;
+pub struct GameWonEvent;
+// a move made by something other than the arrow keys
+pub struct MoveEvent(pub Direction);
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HistoryEvent {
+    Undo,
+    Redo,
+}
+// load the saved game at this path in place of the
+// current one
+pub struct LoadGameEvent(pub PathBuf);
+pub struct AutosaveEvent;
+
+// a saved game waiting to be rebuilt the next time
+// Playing is entered, instead of dealing a new board
+#[derive(Default)]
+struct PendingGame(Option<SavedGame>);
+
+// the game being played as a replay, written out once
+// the game ends and kept in the autosave until then
+struct Recording {
+    replay: Option<Replay>,
+    started: Instant,
+}
+
+impl Default for Recording {
+    fn default() -> Self {
+        Recording {
+            replay: None,
+            started: Instant::now(),
+        }
+    }
+}
+
+impl Recording {
+    fn start(&mut self, replay: Replay) {
+        self.replay = Some(replay);
+        self.started = Instant::now();
+    }
+
+    // carry on with a replay saved along with the game,
+    // its times picking up from its last move
+    fn resume(&mut self, replay: Replay) {
+        let elapsed = replay
+            .times
+            .as_ref()
+            .and_then(|times| times.last())
+            .map_or(Duration::ZERO, |last| Duration::from_millis(*last));
+        self.started = Instant::now()
+            .checked_sub(elapsed)
+            .unwrap_or_else(Instant::now);
+        self.replay = Some(replay);
+    }
+
+    // the replay up to the game's current move, without
+    // any moves that were undone
+    fn in_progress(&self, moves: u32) -> Option<Replay> {
+        let mut replay = self.replay.clone()?;
+        replay.truncate(moves.saturating_sub(replay.start_moves()) as usize);
+        Some(replay)
+    }
 
-pub struct NewTileEvent;
+    // `moves` is the game's move count before `direction`
+    // was played, anything recorded past it was undone
+    fn record(&mut self, moves: u32, direction: Direction) {
+        if let Some(replay) = &mut self.replay {
+            replay.truncate(moves.saturating_sub(replay.start_moves()) as usize);
+            replay.push(direction, self.started.elapsed());
+        }
+    }
+
+    fn finish(&mut self, game: &Game, max_tile: u32) {
+        let mut replay = match self.replay.take() {
+            Some(replay) => replay,
+            None => return,
+        };
+        replay.truncate(game.moves.saturating_sub(replay.start_moves()) as usize);
+        if replay.moves.is_empty() {
+            return;
+        }
+        replay.result = Some(ReplayResult {
+            score: game.score,
+            max_tile,
+        });
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|now| now.as_secs())
+            .unwrap_or(0);
+        let path = Replay::dir().join(format!("{}-{}.ron", now, replay.seed));
+        match replay.save(&path) {
+            Ok(()) => info!("saved replay to {}", path.display()),
+            Err(err) => warn!("couldn't save replay to {}: {}", path.display(), err),
+        }
+    }
+}
 
 struct Materials {
     board: Handle<ColorMaterial>,
@@ -24,54 +143,350 @@ struct Materials {
 }
 
 fn main() {
-    App::build()
-        .insert_resource(WindowDescriptor {
-            title: "2048".to_string(),
-            ..Default::default()
+    let mut options = Options::parse(std::env::args().skip(1)).unwrap_or_else(|err| {
+        eprintln!("{}, see --help", err);
+        std::process::exit(2);
+    });
+    if options.help {
+        print!("{}", HELP);
+        return;
+    }
+    let presets = Presets::load(options.config.as_deref()).unwrap_or_else(|err| {
+        eprintln!("couldn't read presets: {}", err);
+        std::process::exit(2);
+    });
+    if options.list_presets {
+        for (name, preset) in presets.iter() {
+            println!("{}: {}", name, preset);
+        }
+        return;
+    }
+    if let Err(err) = options.use_preset(&presets) {
+        eprintln!("{}", err);
+        std::process::exit(2);
+    }
+
+    let spawn_rules = options.spawn_rules();
+    let replay = options.replay.as_ref().map(|path| {
+        let replay = Replay::load(path).unwrap_or_else(|err| {
+            exit_with(&format!("couldn't load replay {}: {}", path.display(), err))
+        });
+        // the board is set up from it before it's played
+        if let Err(err) = replay.initial() {
+            exit_with(&format!("couldn't play replay {}: {}", path.display(), err));
+        }
+        replay
+    });
+    let position = match (&options.position, &options.position_file) {
+        (Some(notation), _) => Some(notation.clone()),
+        (None, Some(path)) => Some(read_position_file(path)),
+        (None, None) => None,
+    }
+    .map(|notation| {
+        notation.parse::<BoardNotation>().unwrap_or_else(|err| {
+            exit_with(&format!("couldn't read position {:?}: {}", notation, err))
+        })
+    });
+    // a replay has to be watched on the board it was
+    // played on, and a position fixes the size too
+    let config = match (&replay, &position, options.size) {
+        (Some(replay), _, _) => BoardConfig::new(replay.width, replay.height),
+        (None, Some(position), _) => {
+            BoardConfig::new(position.grid.width(), position.grid.height())
+        }
+        (None, None, Some((width, height))) => BoardConfig::new(width, height),
+        (None, None, None) => BoardConfig::default(),
+    };
+    let game_rng = options
+        .seed
+        .or_else(|| position.as_ref().and_then(|position| position.seed))
+        .map_or_else(GameRng::default, GameRng::new);
+    let start = position.map(|position| Snapshot {
+        grid: position.grid,
+        score: position.score.unwrap_or(0),
+        moves: 0,
+        rng: game_rng.state(),
+    });
+
+    if options.headless {
+        run_headless(replay, start, &config, &spawn_rules, game_rng);
+        return;
+    }
+
+    let playback = replay.map(|replay| {
+        Playback::new(replay)
+            .unwrap_or_else(|err| exit_with(&format!("couldn't play replay: {}", err)))
+    });
+    // the position is set up like a saved game so that
+    // it goes through the same path when Playing starts
+    let pending = PendingGame(start.map(|start| {
+        let state = if start.grid.has_legal_move() {
+            RunState::Playing
+        } else {
+            RunState::GameOver
+        };
+        SavedGame::new(start, false, state)
+    }));
+    let initial_state = if playback.is_some() {
+        RunState::Replay
+    } else {
+        RunState::Playing
+    };
+
+    let mut app = App::build();
+    if let Some(playback) = playback {
+        app.insert_resource(playback);
+    }
+    app.insert_resource(WindowDescriptor {
+        title: options.title.clone().unwrap_or_else(|| "2048".to_string()),
+        ..Default::default()
+    })
+    .insert_resource(ClearColor(
+        palette(options.theme.unwrap_or(Theme::Dark)).clear,
+    ))
+    .init_resource::<Game>()
+    .insert_resource(spawn_rules.clone())
+    .insert_resource(game_rng)
+    .insert_resource(options.target.map_or_else(WinTarget::default, WinTarget))
+    .insert_resource(History::new(options.undo_limit))
+    .insert_resource(Autoplay::new(&options, spawn_rules, &config))
+    .insert_resource(config)
+    .insert_resource(pending)
+    .init_resource::<Recording>()
+    .insert_resource(options)
+    .add_startup_system(setup.system())
+    .add_startup_system(load_autosave.system().label("autosave"))
+    .add_startup_system(load_best_scores.system().after("autosave"))
+    // .add_startup_system(setup_ui.system())
+    .add_plugins(DefaultPlugins)
+    .add_plugin(GameUiPlugin)
+    .add_plugin(bevy_easings::EasingsPlugin)
+    .add_startup_stage("board_setup", SystemStage::single(spawn_board.system()))
+    .add_state(initial_state)
+    .add_system(save_best_score.system())
+    .add_system(load_game.system())
+    .add_system_to_stage(CoreStage::PostUpdate, autosave.system())
+    .add_system_set(
+        SystemSet::on_update(RunState::Playing)
+            .with_system(board_shift.system().label("shift"))
+            .with_system(render_blocks.system())
+            // run before the shift so a game that ended last
+            // frame isn't played on
+            .with_system(end_game.system().before("shift"))
+            .with_system(win_game.system().before("shift"))
+            .with_system(undo_redo.system())
+            .with_system(autoplay.system().before("shift")),
+    )
+    // setup when entering the state
+    .add_system_set(
+        SystemSet::on_enter(RunState::Playing)
+            .with_system(game_reset.system().label("reset"))
+            .with_system(spawn_tiles.system().after("reset")),
+    )
+    .add_system_set(SystemSet::on_enter(RunState::Replay).with_system(start_playback.system()))
+    .add_system_set(
+        SystemSet::on_update(RunState::Replay)
+            .with_system(advance_playback.system())
+            .with_system(render_blocks.system()),
+    )
+    .add_system_set(
+        SystemSet::on_enter(RunState::GameOver)
+            .with_system(start_pending_game.system())
+            .with_system(save_replay.system())
+            .with_system(stop_autoplay.system()),
+    )
+    .add_event::<GameOverEvent>()
+    .add_event::<GameWonEvent>()
+    .add_event::<MoveEvent>()
+    .add_event::<HistoryEvent>()
+    .add_event::<PlaybackEvent>()
+    .add_event::<LoadGameEvent>()
+    .add_event::<AutosaveEvent>()
+    .run();
+}
+
+// a bad option is the player's mistake, not a bug, so
+// it gets a message instead of a panic
+fn exit_with(message: &str) -> ! {
+    eprintln!("{}", message);
+    std::process::exit(2);
+}
+
+// the first line that isn't blank or a `#` comment, so
+// puzzle files can say what they're about
+fn read_position_file(path: &Path) -> String {
+    let contents = std::fs::read_to_string(path).unwrap_or_else(|err| {
+        exit_with(&format!(
+            "couldn't read --position-file {}: {}",
+            path.display(),
+            err
+        ))
+    });
+    contents
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with('#'))
+        .unwrap_or_else(|| {
+            exit_with(&format!(
+                "--position-file {} has no position in it",
+                path.display()
+            ))
         })
-        .insert_resource(ClearColor(Color::rgb(0.04, 0.04, 0.1)))
-        .init_resource::<Game>()
-        .add_startup_system(setup.system())
-        // .add_startup_system(setup_ui.system())
-        .add_plugins(DefaultPlugins)
-        .add_plugin(GameUiPlugin)
-        .add_plugin(bevy_easings::EasingsPlugin)
-        .add_startup_stage("board_setup", SystemStage::single(spawn_board.system()))
-        .add_state(RunState::Playing)
-        .add_system_set(
-            SystemSet::on_update(RunState::Playing)
-                .with_system(board_shift.system())
-                .with_system(render_blocks.system())
-                .with_system(new_tile_handler.system()),
-        )
-        // setup when entering the state
-        .add_system_set(
-            SystemSet::on_enter(RunState::Playing)
-                .with_system(game_reset.system().label("reset"))
-                .with_system(spawn_tiles.system().after("reset")),
-        )
-        .add_event::<NewTileEvent>()
-        .run();
-}
-
-fn setup(mut commands: Commands, mut materials: ResMut<Assets<ColorMaterial>>) {
+        .to_string()
+}
+
+// `--headless` prints where the game would start, or
+// where a replay ends up, instead of opening a window
+fn run_headless(
+    replay: Option<Replay>,
+    start: Option<Snapshot>,
+    config: &BoardConfig,
+    spawn_rules: &SpawnRules,
+    mut game_rng: GameRng,
+) {
+    let snapshot = match (replay, start) {
+        (Some(replay), _) => {
+            let mut snapshots = replay
+                .snapshots()
+                .unwrap_or_else(|err| exit_with(&format!("couldn't play replay: {}", err)));
+            snapshots.pop().expect("there to be an initial snapshot")
+        }
+        (None, Some(start)) => start,
+        (None, None) => {
+            let mut grid = Grid::new(config.width, config.height);
+            spawn_rules.spawn(&mut grid, spawn_rules.starting_tiles, &mut game_rng);
+            Snapshot {
+                grid,
+                score: 0,
+                moves: 0,
+                rng: game_rng.state(),
+            }
+        }
+    };
+    print!("{}", snapshot.grid);
+    println!(
+        "{}",
+        BoardNotation {
+            grid: snapshot.grid,
+            score: Some(snapshot.score),
+            seed: Some(snapshot.rng.seed),
+        }
+    );
+}
+
+pub struct Palette {
+    pub clear: Color,
+    board: Color,
+    tile_placeholder: Color,
+    block: Color,
+    // for text drawn straight onto the background
+    pub text: Color,
+}
+
+pub fn palette(theme: Theme) -> Palette {
+    match theme {
+        Theme::Dark => Palette {
+            clear: Color::rgb(0.04, 0.04, 0.1),
+            board: Color::rgb(0.7, 0.7, 0.8),
+            tile_placeholder: Color::rgb(0.75, 0.75, 0.9),
+            block: Color::rgb(0.9, 0.9, 1.0),
+            text: Color::WHITE,
+        },
+        Theme::Light => Palette {
+            clear: Color::rgb(0.98, 0.97, 0.94),
+            board: Color::rgb(0.73, 0.68, 0.63),
+            tile_placeholder: Color::rgb(0.8, 0.75, 0.71),
+            block: Color::rgb(0.93, 0.89, 0.85),
+            text: Color::rgb(0.47, 0.43, 0.4),
+        },
+    }
+}
+
+fn setup(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    options: Res<Options>,
+) {
     commands.spawn_bundle(OrthographicCameraBundle::new_2d());
     commands.spawn_bundle(UiCameraBundle::default());
 
+    let palette = palette(options.theme.unwrap_or(Theme::Dark));
     commands.insert_resource(Materials {
-        board: materials.add(Color::rgb(0.7, 0.7, 0.8).into()),
-        tile_placeholder: materials.add(Color::rgb(0.75, 0.75, 0.9).into()),
-        block: materials.add(Color::rgb(0.9, 0.9, 1.0).into()),
+        board: materials.add(palette.board.into()),
+        tile_placeholder: materials.add(palette.tile_placeholder.into()),
+        block: materials.add(palette.block.into()),
     });
 }
 
-fn spawn_board(mut commands: Commands, materials: Res<Materials>) {
-    let board = Board { size: 4 };
-    let physical_board_size = {
+// `--continue` picks the last game back up, which has
+// to happen before the board is spawned in case the saved
+// game was played on a different size
+fn load_autosave(
+    mut pending: ResMut<PendingGame>,
+    mut config: ResMut<BoardConfig>,
+    options: Res<Options>,
+) {
+    // a `--position` asked for explicitly wins
+    if !options.continue_game || pending.0.is_some() {
+        return;
+    }
+    let path = SavedGame::autosave_path();
+    match SavedGame::load(&path) {
+        Ok(saved) => {
+            *config = BoardConfig::new(saved.snapshot.grid.width(), saved.snapshot.grid.height());
+            pending.0 = Some(saved);
+        }
+        Err(err) => warn!("couldn't continue from {}: {}", path.display(), err),
+    }
+}
+
+fn load_best_scores(
+    mut commands: Commands,
+    mut game: ResMut<Game>,
+    config: Res<BoardConfig>,
+    spawn_rules: Res<SpawnRules>,
+) {
+    let best_scores = BestScores::load().unwrap_or_else(|err| {
+        warn!(
+            "ignoring best scores in {}: {}",
+            BestScores::path().display(),
+            err
+        );
+        BestScores::default()
+    });
+    game.score_best = best_scores.get(&rules_key(config.width, config.height, &spawn_rules));
+    commands.insert_resource(best_scores);
+}
+
+fn save_best_score(
+    game: Res<Game>,
+    mut best_scores: ResMut<BestScores>,
+    config: Res<BoardConfig>,
+    spawn_rules: Res<SpawnRules>,
+) {
+    if !game.is_changed() {
+        return;
+    }
+    let key = rules_key(config.width, config.height, &spawn_rules);
+    if let Err(err) = best_scores.update_and_save(&key, game.score_best) {
+        warn!(
+            "couldn't save best scores to {}: {}",
+            BestScores::path().display(),
+            err
+        );
+    }
+}
+
+fn spawn_board(mut commands: Commands, materials: Res<Materials>, config: Res<BoardConfig>) {
+    let board = Board {
+        width: config.width,
+        height: config.height,
+    };
+    let physical_length = |tiles: u8| {
         // size of all tiles
-        f32::from(board.size) * TILE_SIZE
+        f32::from(tiles) * TILE_SIZE
         // size of all spacers
-        + f32::from(board.size) * TILE_SPACER
+        + f32::from(tiles) * TILE_SPACER
         // extra spacer on the off side to round out the board
         + TILE_SPACER
     };
@@ -79,33 +494,20 @@ fn spawn_board(mut commands: Commands, materials: Res<Materials>) {
     commands
         .spawn_bundle(SpriteBundle {
             material: materials.board.clone(),
-            sprite: Sprite::new(Vec2::new(physical_board_size, physical_board_size)),
+            sprite: Sprite::new(Vec2::new(
+                physical_length(board.width),
+                physical_length(board.height),
+            )),
             ..Default::default()
         })
         .with_children(|child_builder| {
-            for tile in (0..board.size).cartesian_product(0..board.size) {
+            for tile in (0..board.width).cartesian_product(0..board.height) {
                 child_builder.spawn_bundle(SpriteBundle {
                     material: materials.tile_placeholder.clone(),
                     sprite: Sprite::new(Vec2::new(TILE_SIZE, TILE_SIZE)),
                     transform: Transform::from_xyz(
-                        // true position
-                        f32::from(tile.0) * TILE_SIZE
-                        // moved left because it is at board center
-                            - (f32::from(board.size)
-                                * TILE_SIZE
-                                / 2.0)
-                                // moved right because it's even numbered
-                                // (odd would be centered)
-                            + (0.5 * TILE_SIZE)
-                            // account for in-between spacing by applying N
-                            // spacers
-                            + f32::from(tile.0)
-                                * TILE_SPACER
-                            - TILE_SPACER * 1.5,
-                        f32::from(tile.1) * TILE_SIZE - (f32::from(board.size) * TILE_SIZE / 2.0)
-                            + (0.5 * TILE_SIZE)
-                            + f32::from(tile.1) * TILE_SPACER
-                            - TILE_SPACER * 1.5,
+                        block_pos_to_transform(board.width, tile.0),
+                        block_pos_to_transform(board.height, tile.1),
                         1.0,
                     ),
                     ..Default::default()
@@ -114,11 +516,24 @@ fn spawn_board(mut commands: Commands, materials: Res<Materials>) {
         })
         .insert(board);
 }
-fn game_reset(mut commands: Commands, blocks: Query<Entity, With<Block>>, mut game: ResMut<Game>) {
-    for entity in blocks.iter() {
+fn game_reset(
+    mut commands: Commands,
+    blocks: Query<(Entity, &Block)>,
+    mut game: ResMut<Game>,
+    mut history: ResMut<History>,
+    mut recording: ResMut<Recording>,
+    game_rng: Res<GameRng>,
+) {
+    // a game left for a new one has ended too
+    recording.finish(&game, max_tile(blocks.iter().map(|(_, block)| block)));
+    info!("starting game with seed {}", game_rng.seed());
+    history.clear();
+    for (entity, _) in blocks.iter() {
         commands.entity(entity).despawn_recursive();
     }
     game.score = 0;
+    game.moves = 0;
+    game.endless = false;
 }
 
 fn spawn_tiles(
@@ -126,60 +541,113 @@ fn spawn_tiles(
     materials: Res<Materials>,
     query_board: Query<&Board>,
     asset_server: Res<AssetServer>,
+    spawn_rules: Res<SpawnRules>,
+    mut game_rng: ResMut<GameRng>,
+    mut pending: ResMut<PendingGame>,
+    mut game: ResMut<Game>,
+    mut run_state: ResMut<State<RunState>>,
+    mut recording: ResMut<Recording>,
 ) {
     let board = query_board.single().expect("always expect a board");
+    if let Some(saved) = pending.0.take() {
+        // a replay that doesn't lead to the saved game, or
+        // was played by other rules, can't be extended
+        let replay = saved
+            .replay
+            .clone()
+            .filter(|replay| replay.rules == *spawn_rules && replay.reaches(&saved.snapshot));
+        match replay {
+            Some(replay) => recording.resume(replay),
+            None => recording.start(Replay::new(
+                saved.snapshot.rng.seed,
+                board.width,
+                board.height,
+                spawn_rules.clone(),
+                Some(saved.snapshot.clone()),
+            )),
+        }
+        restore_snapshot(
+            &mut commands,
+            &materials,
+            &asset_server,
+            board,
+            &saved.snapshot,
+            &mut game,
+            &mut game_rng,
+        );
+        game.endless = saved.endless;
+        match saved.state {
+            // neither the slot panel nor a replay is ever
+            // saved as the game's state
+            RunState::Playing | RunState::Slots | RunState::Replay => {}
+            RunState::GameOver => run_state.set(RunState::GameOver).unwrap(),
+            RunState::Won => run_state.push(RunState::Won).unwrap(),
+        }
+        return;
+    }
+    recording.start(Replay::new(
+        game_rng.seed(),
+        board.width,
+        board.height,
+        spawn_rules.clone(),
+        None,
+    ));
     // insert new tile
-    let mut rng = rand::thread_rng();
-    let starting_tiles: Vec<Position> = (0..board.size)
-        .cartesian_product(0..board.size)
-        .map(|tile_pos| Position {
-            x: tile_pos.0,
-            y: tile_pos.1,
+    let mut grid = Grid::new(board.width, board.height);
+    for (pos, value) in spawn_rules.spawn(&mut grid, spawn_rules.starting_tiles, &mut *game_rng) {
+        spawn_block(&mut commands, &materials, &asset_server, board, pos, value);
+    }
+}
+
+fn spawn_block(
+    commands: &mut Commands,
+    materials: &Materials,
+    asset_server: &AssetServer,
+    board: &Board,
+    pos: Position,
+    value: u32,
+) {
+    commands
+        .spawn_bundle(SpriteBundle {
+            material: materials.block.clone(),
+            sprite: Sprite::new(Vec2::new(TILE_SIZE, TILE_SIZE)),
+            transform: Transform::from_xyz(
+                block_pos_to_transform(board.width, pos.x),
+                block_pos_to_transform(board.height, pos.y),
+                1.0,
+            ),
+            ..Default::default()
         })
-        .choose_multiple(&mut rng, 2);
-    for Position { x, y } in starting_tiles.iter() {
-        let pos = Position { x: *x, y: *y };
-        commands
-            .spawn_bundle(SpriteBundle {
-                material: materials.block.clone(),
-                sprite: Sprite::new(Vec2::new(TILE_SIZE, TILE_SIZE)),
-                transform: Transform::from_xyz(
-                    block_pos_to_transform(board.size, pos.x),
-                    block_pos_to_transform(board.size, pos.y),
-                    1.0,
-                ),
-                ..Default::default()
-            })
-            .with_children(|child_builder| {
-                child_builder
-                    .spawn_bundle(Text2dBundle {
-                        text: Text::with_section(
-                            "2",
-                            TextStyle {
-                                font: asset_server.load("fonts/FiraSans-Bold.ttf"),
-                                font_size: 40.0,
-                                color: Color::BLACK,
-                                ..Default::default()
-                            },
-                            TextAlignment {
-                                vertical: VerticalAlign::Center,
-                                horizontal: HorizontalAlign::Center,
-                            },
-                        ),
-                        transform: Transform::from_xyz(0.0, 0.0, 1.0),
-                        ..Default::default()
-                    })
-                    .insert(BlockText);
-            })
-            .insert(Block { value: 2 })
-            .insert(pos);
-    }
-}
-
-fn block_pos_to_transform(board_size: u8, pos: u8) -> f32 {
+        .with_children(|child_builder| {
+            child_builder
+                .spawn_bundle(Text2dBundle {
+                    text: Text::with_section(
+                        value.to_string(),
+                        TextStyle {
+                            font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                            font_size: 40.0,
+                            color: Color::BLACK,
+                        },
+                        TextAlignment {
+                            vertical: VerticalAlign::Center,
+                            horizontal: HorizontalAlign::Center,
+                        },
+                    ),
+                    transform: Transform::from_xyz(0.0, 0.0, 1.0),
+                    ..Default::default()
+                })
+                .insert(BlockText);
+        })
+        .insert(Block { value })
+        .insert(pos);
+}
+
+// `board_length` is the width or height of the board,
+// whichever matches the axis `pos` is on
+fn block_pos_to_transform(board_length: u8, pos: u8) -> f32 {
     f32::from(pos) * TILE_SIZE
         // moved left because it is at board center
-            - (f32::from(board_size)
+            - (f32::from(board_length)
                 * TILE_SIZE
                 / 2.0)
                 // moved right because it's even numbered
@@ -189,489 +657,358 @@ fn block_pos_to_transform(board_size: u8, pos: u8) -> f32 {
             // spacers
             + f32::from(pos)
                 * TILE_SPACER
-        - TILE_SPACER * 1.5
+        // and back by half of the spacers between tiles so
+        // the whole board stays centered
+        - (f32::from(board_length) - 1.0) * TILE_SPACER / 2.0
 }
 fn render_blocks(
     mut commands: Commands,
     mut blocks: Query<(Entity, &mut Transform, &Position, Changed<Position>), With<Block>>,
     query_board: Query<&Board>,
+    options: Res<Options>,
 ) {
     let board = query_board.single().expect("expect there to be a board");
+    let duration = std::time::Duration::from_millis(options.animation_ms.unwrap_or(ANIMATION_MS));
     for (entity, transform, pos, pos_changed) in blocks.iter_mut() {
         if pos_changed {
-            let x = block_pos_to_transform(board.size, pos.x);
-            let y = block_pos_to_transform(board.size, pos.y);
+            let x = block_pos_to_transform(board.width, pos.x);
+            let y = block_pos_to_transform(board.height, pos.y);
             let mut ent = commands.entity(entity);
             ent.insert(transform.ease_to(
                 Transform::from_xyz(x, y, transform.translation.z),
                 EaseFunction::QuadraticInOut,
-                EasingType::Once {
-                    duration: std::time::Duration::from_millis(100),
-                },
+                EasingType::Once { duration },
             ));
         }
     }
 }
 
-#[derive(Debug)]
-enum MergeStatus {
-    Merge,
-    DifferentRows,
-    DifferentValues,
-}
-// u32 is the block value
-// u8 is the "vertical" y position,
-// which is different for each direction
-fn should_merge(block: (u32, u8), block_next: (u32, u8)) -> MergeStatus {
-    if block.1 != block_next.1 {
-        // if the blocks aren't on the same vertical level
-        // they can't collide, so skip to next loop
-        // iteration
-        MergeStatus::DifferentRows
-    } else if block.0 != block_next.0 {
-        // if the block values don't match,
-        // they can't merge, so skip to the
-        // next loop iteration
-        MergeStatus::DifferentValues
-    } else {
-        MergeStatus::Merge
-    }
+fn pressed_direction(keyboard_input: &Input<KeyCode>) -> Option<Direction> {
+    KEY_BINDINGS
+        .iter()
+        .find(|(key, _)| keyboard_input.just_pressed(*key))
+        .map(|(_, direction)| *direction)
 }
+
 fn board_shift(
     mut commands: Commands,
     keyboard_input: Res<Input<KeyCode>>,
-    // mut query_world: Query<&mut World>,
-    mut texts: Query<(Entity, &mut Text), With<BlockText>>,
+    mut move_reader: EventReader<MoveEvent>,
+    mut texts: Query<&mut Text, With<BlockText>>,
     mut blocks: Query<(Entity, &mut Position, &mut Block, &Children)>,
     query_board: Query<&Board>,
-    mut tile_writer: EventWriter<NewTileEvent>,
+    asset_server: Res<AssetServer>,
+    materials: Res<Materials>,
     mut game: ResMut<Game>,
-    mut run_state: ResMut<State<RunState>>,
+    mut history: ResMut<History>,
+    mut recording: ResMut<Recording>,
+    mut game_rng: ResMut<GameRng>,
+    spawn_rules: Res<SpawnRules>,
+    win_target: Res<WinTarget>,
+    // bevy only takes so many parameters
+    (mut game_won_writer, mut game_over_writer): (
+        EventWriter<GameWonEvent>,
+        EventWriter<GameOverEvent>,
+    ),
+    mut autosave_writer: EventWriter<AutosaveEvent>,
 ) {
-    // Normal Processing
     let board = query_board.single().expect("expect there to be a board");
 
-    // EndGameCheck
-    if blocks.iter_mut().len() == 16 {
-        let mut map: HashMap<(u8, u8), u32> = HashMap::new();
-        for tile in (0..board.size).cartesian_product(0..board.size) {
-            map.insert(tile, 0);
-        }
-        for (_, position, block, _) in blocks.iter_mut() {
-            map.insert((position.x, position.y), block.value);
+    let moved = move_reader
+        .iter()
+        .last()
+        .map(|MoveEvent(direction)| *direction);
+    let direction = match pressed_direction(&keyboard_input).or(moved) {
+        Some(direction) => direction,
+        None => return,
+    };
+
+    let mut entities: HashMap<Position, Entity> = HashMap::new();
+    let before = current_snapshot(
+        board,
+        blocks.iter_mut().map(|(entity, position, block, _)| {
+            entities.insert(*position, entity);
+            (*position, block.value)
+        }),
+        &game,
+        &game_rng,
+    );
+    let turn = match before.play(direction, &spawn_rules) {
+        Some(turn) => turn,
+        None => return,
+    };
+    history.record(before);
+    recording.record(game.moves, direction);
+
+    move_blocks(
+        &mut commands,
+        &mut texts,
+        &mut blocks,
+        &entities,
+        &turn.outcome,
+    );
+    for &(pos, value) in turn.spawned.iter() {
+        spawn_block(&mut commands, &materials, &asset_server, board, pos, value);
+    }
+    game.score = turn.snapshot.score;
+    game.moves = turn.snapshot.moves;
+    // the rng moves on with the tiles it dealt
+    game_rng.restore(turn.snapshot.rng);
+
+    // the state changes next frame, once the new tiles
+    // are on the board
+    match turn.state(&win_target, game.endless) {
+        RunState::Won => game_won_writer.send(GameWonEvent),
+        RunState::GameOver => game_over_writer.send(GameOverEvent),
+        _ => {}
+    }
+    autosave_writer.send(AutosaveEvent);
+
+    if game.score_best < game.score {
+        game.score_best = game.score;
+    }
+}
+
+// make the block entities follow a move, `entities` maps
+// each tile's position before the move to its block
+fn move_blocks(
+    commands: &mut Commands,
+    texts: &mut Query<&mut Text, With<BlockText>>,
+    blocks: &mut Query<(Entity, &mut Position, &mut Block, &Children)>,
+    entities: &HashMap<Position, Entity>,
+    outcome: &MoveOutcome,
+) {
+    for slide in outcome.slides.iter() {
+        let (_, mut position, _, _) = blocks
+            .get_mut(entities[&slide.from])
+            .expect("every tile in the grid to have a block");
+        *position = slide.to;
+    }
+    for merge in outcome.merges.iter() {
+        let (_, mut position, mut block, children) = blocks
+            .get_mut(entities[&merge.kept])
+            .expect("every tile in the grid to have a block");
+        if *position != merge.to {
+            *position = merge.to;
         }
-        let has_move = map
-            .iter()
-            .find(|((x, y), value)| {
-                vec![(-1, 0), (0, 1), (1, 0), (0, -1)]
-                    .iter()
-                    .flat_map(|(x2, y2)| {
-                        let new_x = (*x as i8) - x2;
-                        let new_y = (*y as i8) - y2;
-
-                        let board_range: Range<i8> = 0..(board.size as i8);
-
-                        if !board_range.contains(&new_x) && !board_range.contains(&new_y) {
-                            return None;
-                        };
-
-                        match (new_x.try_into(), new_y.try_into()) {
-                            (Ok(x), Ok(y)) => Some(map.get(&(x, y))),
-                            _ => None,
-                        }
-                    })
-                    .filter_map(|x| x)
-                    .any(|v| v == *value)
-            })
-            .is_some();
-
-        if has_move == false {
-            run_state.set(RunState::GameOver).unwrap();
+        block.value = merge.value;
+        // update text
+        for child in children.iter() {
+            let mut text = texts.get_mut(*child).expect("text to exist");
+            let section = text
+                .sections
+                .first_mut()
+                .expect("expect a single section in text");
+            section.value = block.value.to_string();
         }
+        commands
+            .entity(entities[&merge.absorbed])
+            .despawn_recursive();
+    }
+}
+
+fn undo_redo(
+    mut commands: Commands,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut history_reader: EventReader<HistoryEvent>,
+    mut history: ResMut<History>,
+    materials: Res<Materials>,
+    asset_server: Res<AssetServer>,
+    query_board: Query<&Board>,
+    blocks: Query<(Entity, &Position, &Block)>,
+    mut game: ResMut<Game>,
+    mut game_rng: ResMut<GameRng>,
+    mut autosave_writer: EventWriter<AutosaveEvent>,
+) {
+    let event = if keyboard_input.just_pressed(UNDO_KEY) {
+        Some(HistoryEvent::Undo)
+    } else if keyboard_input.just_pressed(REDO_KEY) {
+        Some(HistoryEvent::Redo)
+    } else {
+        history_reader.iter().last().copied()
+    };
+    let event = match event {
+        Some(event) => event,
+        None => return,
     };
 
-    if keyboard_input.just_pressed(KeyCode::Left) {
-        let mut it = blocks
-            .iter_mut()
-            .sorted_by(|a, b| match Ord::cmp(&a.1.y, &b.1.y) {
-                std::cmp::Ordering::Equal => Ord::cmp(&a.1.x, &b.1.x),
-                ordering => ordering,
-            })
-            // .sorted_by_key(|v| v.1.y)
-            .peekable();
-        let mut x: u8 = 0;
-
-        loop {
-            match (it.next(), it.peek()) {
-                (None, _) => {
-                    // nothing left to process
-                    break;
-                }
-                (Some(mut block), None) => {
-                    block.1.x = x;
-                }
-                (Some(mut block), Some(block_next)) => {
-                    match should_merge(
-                        (block.2.value, block.1.y),
-                        (block_next.2.value, block_next.1.y),
-                    ) {
-                        MergeStatus::Merge => {
-                            // despawn the next block, and
-                            // merge it with the current
-                            // block.
-                            let real_next_block = it
-                                .next()
-                                .expect("A peeked block should always exist when we .next here");
-                            block.2.value = block.2.value + real_next_block.2.value;
-                            block.1.x = x;
-
-                            // update score
-                            game.score += block.2.value;
-                            // update text
-                            for child in block.3.iter() {
-                                let mut text = texts.get_mut(*child).expect("text to exist");
-                                let mut section = text
-                                    .1
-                                    .sections
-                                    .first_mut()
-                                    .expect("expect a single section in text");
-                                section.value = block.2.value.to_string();
-                            }
-                            // if the next, next block
-                            // (block #3 of 3)
-                            // isn't in the same row, reset
-                            // x
-                            // otherwise increment by one
-                            if let Some(future) = it.peek() {
-                                if block.1.y != future.1.y {
-                                    x = 0;
-                                } else {
-                                    x = x + 1;
-                                }
-                            }
-
-                            commands.entity(real_next_block.0).despawn_recursive();
-                            continue;
-                        }
-                        MergeStatus::DifferentRows => {
-                            block.1.x = x;
-                            x = 0;
-                            continue;
-                        }
-                        MergeStatus::DifferentValues => {
-                            block.1.x = x;
-                            x = x + 1;
-                            continue;
-                        }
-                    }
-                }
-            }
-            break;
-        }
-        // insert new block
-        tile_writer.send(NewTileEvent);
-    } else if keyboard_input.just_pressed(KeyCode::Right) {
-        let mut it = blocks
-            .iter_mut()
-            // we want our sorting to first sort by x,
-            // then break x ties with y's comparison
-            .sorted_by(|a, b| match Ord::cmp(&b.1.y, &a.1.y) {
-                std::cmp::Ordering::Equal => Ord::cmp(&b.1.x, &a.1.x),
-                a => a,
-            })
-            .peekable();
-        let mut x: u8 = 0;
-        loop {
-            match (it.next(), it.peek()) {
-                (None, _) => {
-                    break;
-                }
-                (Some(mut block), None) => {
-                    block.1.x = board.size - 1 - x;
-                }
-                (Some(mut block), Some(block_next)) => {
-                    match should_merge(
-                        (block.2.value, block.1.y),
-                        (block_next.2.value, block_next.1.y),
-                    ) {
-                        MergeStatus::Merge => {
-                            let real_next_block = it
-                                .next()
-                                .expect("A peeked block should always exist when we .next here");
-                            block.2.value = block.2.value + real_next_block.2.value;
-                            block.1.x = board.size - 1 - x;
-
-                            // update score
-                            game.score += block.2.value;
-                            // update text
-                            for child in block.3.iter() {
-                                let mut text = texts.get_mut(*child).expect("text to exist");
-                                let mut section = text
-                                    .1
-                                    .sections
-                                    .first_mut()
-                                    .expect("expect a single section in text");
-                                section.value = block.2.value.to_string();
-                            }
-
-                            if let Some(future) = it.peek() {
-                                if block.1.y != future.1.y {
-                                    x = 0;
-                                } else {
-                                    x = x + 1;
-                                }
-                            }
-                            commands.entity(real_next_block.0).despawn_recursive();
-                            continue;
-                        }
-                        MergeStatus::DifferentRows => {
-                            block.1.x = board.size - 1 - x;
-                            x = 0;
-                            continue;
-                        }
-                        MergeStatus::DifferentValues => {
-                            block.1.x = board.size - 1 - x;
-                            x = x + 1;
-                            continue;
-                        }
-                    }
-                }
-            }
+    let board = query_board.single().expect("expect there to be a board");
+    let current = current_snapshot(
+        board,
+        blocks.iter().map(|(_, pos, block)| (*pos, block.value)),
+        &game,
+        &game_rng,
+    );
+    let snapshot = match event {
+        HistoryEvent::Undo => history.undo(current),
+        HistoryEvent::Redo => history.redo(current),
+    };
 
-            break;
+    if let Some(snapshot) = snapshot {
+        for (entity, _, _) in blocks.iter() {
+            commands.entity(entity).despawn_recursive();
         }
-        // insert new block
-        tile_writer.send(NewTileEvent);
-    } else if keyboard_input.just_pressed(KeyCode::Down) {
-        let mut it = blocks
-            .iter_mut()
-            .sorted_by(|a, b| match Ord::cmp(&a.1.x, &b.1.x) {
-                std::cmp::Ordering::Equal => Ord::cmp(&a.1.y, &b.1.y),
-                ordering => ordering,
-            })
-            .peekable();
-        let mut y: u8 = 0;
-
-        loop {
-            match (it.next(), it.peek()) {
-                (None, _) => {
-                    // nothing left to process
-                    break;
-                }
-                (Some(mut block), None) => {
-                    block.1.y = y;
-                }
-                (Some(mut block), Some(block_next)) => {
-                    match should_merge(
-                        (block.2.value, block.1.x),
-                        (block_next.2.value, block_next.1.x),
-                    ) {
-                        MergeStatus::Merge => {
-                            // _recursive the next block,
-                            // and
-                            // merge it with the current
-                            // block.
-                            let real_next_block = it
-                                .next()
-                                .expect("A peeked block should always exist when we .next here");
-                            block.2.value = block.2.value + real_next_block.2.value;
-                            block.1.y = y;
-
-                            // update score
-                            game.score += block.2.value;
-                            // update text
-                            for child in block.3.iter() {
-                                let mut text = texts.get_mut(*child).expect("text to exist");
-                                let mut section = text
-                                    .1
-                                    .sections
-                                    .first_mut()
-                                    .expect("expect a single section in text");
-                                section.value = block.2.value.to_string();
-                            }
-                            // if the next, next block
-                            // (block #3 of 3)
-                            // isn't in the same row, reset
-                            // x
-                            // otherwise increment by one
-                            if let Some(future) = it.peek() {
-                                if block.1.x != future.1.x {
-                                    y = 0;
-                                } else {
-                                    y = y + 1;
-                                }
-                            }
-                            commands.entity(real_next_block.0).despawn_recursive();
-                            continue;
-                        }
-                        MergeStatus::DifferentRows => {
-                            block.1.y = y;
-                            y = 0;
-                            continue;
-                        }
-                        MergeStatus::DifferentValues => {
-                            block.1.y = y;
-                            y = y + 1;
-                            continue;
-                        }
-                    }
-                }
-            }
-            break;
-        }
-        // insert new block
-        tile_writer.send(NewTileEvent);
-    } else if keyboard_input.just_pressed(KeyCode::Up) {
-        let mut it = blocks
-            .iter_mut()
-            .sorted_by(|a, b| match Ord::cmp(&b.1.x, &a.1.x) {
-                std::cmp::Ordering::Equal => Ord::cmp(&b.1.y, &a.1.y),
-                ordering => ordering,
-            })
-            .peekable();
-        let mut y: u8 = 0;
-
-        loop {
-            match (it.next(), it.peek()) {
-                (None, _) => {
-                    // nothing left to process
-                    break;
-                }
-                (Some(mut block), None) => {
-                    block.1.y = board.size - 1 - y;
-                }
-                (Some(mut block), Some(block_next)) => {
-                    match should_merge(
-                        (block.2.value, block.1.x),
-                        (block_next.2.value, block_next.1.x),
-                    ) {
-                        MergeStatus::Merge => {
-                            // despawn the next block, and
-                            // merge it with the current
-                            // block.
-                            let real_next_block = it
-                                .next()
-                                .expect("A peeked block should always exist when we .next here");
-                            block.2.value = block.2.value + real_next_block.2.value;
-                            block.1.y = board.size - 1 - y;
-
-                            // update score
-                            game.score += block.2.value;
-                            // update text
-                            for child in block.3.iter() {
-                                let mut text = texts.get_mut(*child).expect("text to exist");
-                                let mut section = text
-                                    .1
-                                    .sections
-                                    .first_mut()
-                                    .expect("expect a single section in text");
-                                section.value = block.2.value.to_string();
-                            }
-                            // if the next, next block
-                            // (block #3 of 3)
-                            // isn't in the same row, reset
-                            // x
-                            // otherwise increment by one
-                            if let Some(future) = it.peek() {
-                                if block.1.x != future.1.x {
-                                    y = 0;
-                                } else {
-                                    y = y + 1;
-                                }
-                            }
-                            commands.entity(real_next_block.0).despawn_recursive();
-                            continue;
-                        }
-                        MergeStatus::DifferentRows => {
-                            block.1.y = board.size - 1 - y;
-                            y = 0;
-                            continue;
-                        }
-                        MergeStatus::DifferentValues => {
-                            block.1.y = board.size - 1 - y;
-                            y = y + 1;
-                            continue;
-                        }
-                    }
-                }
-            }
-            break;
+        restore_snapshot(
+            &mut commands,
+            &materials,
+            &asset_server,
+            board,
+            &snapshot,
+            &mut game,
+            &mut game_rng,
+        );
+        autosave_writer.send(AutosaveEvent);
+    }
+}
+
+fn current_snapshot(
+    board: &Board,
+    tiles: impl IntoIterator<Item = (Position, u32)>,
+    game: &Game,
+    game_rng: &GameRng,
+) -> Snapshot {
+    Snapshot {
+        grid: Grid::from_tiles(board.width, board.height, tiles),
+        score: game.score,
+        moves: game.moves,
+        rng: game_rng.state(),
+    }
+}
+
+// rebuild the block entities for a snapshot, the caller
+// is responsible for clearing out the old ones
+fn restore_snapshot(
+    commands: &mut Commands,
+    materials: &Materials,
+    asset_server: &AssetServer,
+    board: &Board,
+    snapshot: &Snapshot,
+    game: &mut Game,
+    game_rng: &mut GameRng,
+) {
+    for (pos, value) in snapshot.grid.tiles() {
+        spawn_block(commands, materials, asset_server, board, pos, value);
+    }
+    game.score = snapshot.score;
+    game.moves = snapshot.moves;
+    game_rng.restore(snapshot.rng);
+}
+
+fn load_game(
+    mut load_reader: EventReader<LoadGameEvent>,
+    mut pending: ResMut<PendingGame>,
+    mut run_state: ResMut<State<RunState>>,
+    query_board: Query<&Board>,
+) {
+    let path = match load_reader.iter().last() {
+        Some(LoadGameEvent(path)) => path,
+        None => return,
+    };
+    let saved = match SavedGame::load(path) {
+        Ok(saved) => saved,
+        Err(err) => {
+            warn!("couldn't load {}: {}", path.display(), err);
+            return;
         }
-        // insert new block
-        tile_writer.send(NewTileEvent);
+    };
+    let board = query_board.single().expect("expect there to be a board");
+    let grid = &saved.snapshot.grid;
+    if (grid.width(), grid.height()) != (board.width, board.height) {
+        warn!(
+            "the saved game is {}x{}, start with --size {}x{} to load it",
+            grid.width(),
+            grid.height(),
+            grid.width(),
+            grid.height()
+        );
+        return;
     }
-    if game.score_best < game.score {
-        game.score_best = game.score;
+
+    // the saved game is rebuilt when Playing is entered
+    pending.0 = Some(saved);
+    match run_state.current() {
+        // Playing can't be re-entered directly, so go
+        // through GameOver which sends us straight back
+        RunState::Playing => run_state.set(RunState::GameOver).unwrap(),
+        RunState::GameOver | RunState::Replay => run_state.set(RunState::Playing).unwrap(),
+        // replacing the stack re-enters Playing even when
+        // it's underneath
+        RunState::Won | RunState::Slots => run_state.replace(RunState::Playing).unwrap(),
     }
 }
 
-fn new_tile_handler(
-    mut tile_reader: EventReader<NewTileEvent>,
-    mut commands: Commands,
+fn save_replay(mut recording: ResMut<Recording>, game: Res<Game>, blocks: Query<&Block>) {
+    recording.finish(&game, max_tile(blocks.iter()));
+}
+
+fn max_tile<'a>(blocks: impl Iterator<Item = &'a Block>) -> u32 {
+    blocks.map(|block| block.value).max().unwrap_or(0)
+}
+
+fn start_pending_game(pending: Res<PendingGame>, mut run_state: ResMut<State<RunState>>) {
+    if pending.0.is_some() {
+        run_state.set(RunState::Playing).unwrap();
+    }
+}
+
+fn autosave(
+    mut autosave_reader: EventReader<AutosaveEvent>,
+    mut exit_reader: EventReader<AppExit>,
+    run_state: Res<State<RunState>>,
     query_board: Query<&Board>,
-    asset_server: Res<AssetServer>,
-    materials: Res<Materials>,
     blocks: Query<(&Position, &Block)>,
+    game: Res<Game>,
+    game_rng: Res<GameRng>,
+    recording: Res<Recording>,
 ) {
-    let board = query_board
-        .single()
-        .expect("expect there to always be a board");
-
-    if tile_reader.iter().next().is_some() {
-        // insert new tile
-        let mut rng = rand::thread_rng();
-        let possible_position: Option<Position> = (0..board.size)
-            .cartesian_product(0..board.size)
-            .filter_map(|tile_pos| {
-                let new_pos = Position {
-                    x: tile_pos.0,
-                    y: tile_pos.1,
-                };
-                match blocks.iter().find(|(pos, _)| **pos == new_pos) {
-                    Some(_) => None,
-                    None => Some(new_pos),
-                }
-            })
-            .choose(&mut rng);
-
-        match possible_position {
-            Some(pos) => {
-                commands
-                    .spawn_bundle(SpriteBundle {
-                        material: materials.block.clone(),
-                        sprite: Sprite::new(Vec2::new(TILE_SIZE, TILE_SIZE)),
-                        transform: Transform::from_xyz(
-                            block_pos_to_transform(board.size, pos.x),
-                            block_pos_to_transform(board.size, pos.y),
-                            1.0,
-                        ),
-                        ..Default::default()
-                    })
-                    .with_children(|child_builder| {
-                        child_builder
-                            .spawn_bundle(Text2dBundle {
-                                text: Text::with_section(
-                                    "2",
-                                    TextStyle {
-                                        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
-                                        font_size: 40.0,
-                                        color: Color::BLACK,
-                                        ..Default::default()
-                                    },
-                                    TextAlignment {
-                                        vertical: VerticalAlign::Center,
-                                        horizontal: HorizontalAlign::Center,
-                                    },
-                                ),
-                                transform: Transform::from_xyz(0.0, 0.0, 1.0),
-                                ..Default::default()
-                            })
-                            .insert(BlockText);
-                    })
-                    .insert(Block { value: 2 })
-                    .insert(pos);
-            }
-            None => (),
-        }
+    let requested = autosave_reader.iter().count() + exit_reader.iter().count() > 0;
+    // a board nobody has touched isn't worth replacing an
+    // older save for
+    if !requested || game.moves == 0 {
+        return;
+    }
+    // every move is saved as it happens, so there's
+    // nothing new to save from behind the slot panel, and
+    // a replay isn't a game to pick back up
+    if matches!(run_state.current(), RunState::Slots | RunState::Replay) {
+        return;
+    }
+    let board = query_board.single().expect("expect there to be a board");
+    let mut saved = SavedGame::new(
+        current_snapshot(
+            board,
+            blocks.iter().map(|(pos, block)| (*pos, block.value)),
+            &game,
+            &game_rng,
+        ),
+        game.endless,
+        run_state.current().clone(),
+    );
+    saved.replay = recording.in_progress(game.moves);
+    let path = SavedGame::autosave_path();
+    if let Err(err) = saved.save(&path) {
+        warn!("couldn't autosave to {}: {}", path.display(), err);
+    }
+}
+
+fn end_game(
+    mut game_over_reader: EventReader<GameOverEvent>,
+    mut run_state: ResMut<State<RunState>>,
+    mut autosave_writer: EventWriter<AutosaveEvent>,
+) {
+    if game_over_reader.iter().next().is_some() {
+        run_state.set(RunState::GameOver).unwrap();
+        autosave_writer.send(AutosaveEvent);
+    }
+}
+
+fn win_game(
+    mut game_won_reader: EventReader<GameWonEvent>,
+    mut run_state: ResMut<State<RunState>>,
+    mut autosave_writer: EventWriter<AutosaveEvent>,
+) {
+    if game_won_reader.iter().next().is_some() {
+        run_state.push(RunState::Won).unwrap();
+        autosave_writer.send(AutosaveEvent);
     }
 }
@@ -0,0 +1,238 @@
+use crate::components::{Position, MAX_BOARD_SIZE, MIN_BOARD_SIZE};
+use crate::grid::Grid;
+use itertools::Itertools;
+use std::fmt;
+use std::str::FromStr;
+
+/// A board written down on one line, e.g.
+/// `4x4 2,.,.,./.,4,.,./.,.,.,./.,.,.,2 score=4 seed=42`.
+///
+/// The size comes first, then the rows from the top of
+/// the board down with `.` for an empty cell, then any
+/// optional `key=value` fields.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BoardNotation {
+    pub grid: Grid,
+    pub score: Option<u32>,
+    pub seed: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NotationError {
+    MissingRows,
+    BadSize(String),
+    WrongRowCount {
+        expected: u8,
+        found: usize,
+    },
+    WrongRowLength {
+        row: usize,
+        expected: u8,
+        found: usize,
+    },
+    BadCell(String),
+    BadField(String),
+}
+
+impl fmt::Display for NotationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NotationError::MissingRows => write!(f, "expected a size followed by rows"),
+            NotationError::BadSize(size) => write!(
+                f,
+                "{:?} is not a board size between {}x{} and {}x{}",
+                size, MIN_BOARD_SIZE, MIN_BOARD_SIZE, MAX_BOARD_SIZE, MAX_BOARD_SIZE
+            ),
+            NotationError::WrongRowCount { expected, found } => {
+                write!(f, "expected {} rows, found {}", expected, found)
+            }
+            NotationError::WrongRowLength {
+                row,
+                expected,
+                found,
+            } => write!(
+                f,
+                "row {} should have {} cells, found {}",
+                row + 1,
+                expected,
+                found
+            ),
+            NotationError::BadCell(cell) => write!(f, "{:?} is not a tile or `.`", cell),
+            NotationError::BadField(field) => write!(f, "{:?} is not a known field", field),
+        }
+    }
+}
+
+impl std::error::Error for NotationError {}
+
+impl BoardNotation {
+    pub fn new(grid: Grid) -> Self {
+        BoardNotation {
+            grid,
+            score: None,
+            seed: None,
+        }
+    }
+}
+
+impl FromStr for BoardNotation {
+    type Err = NotationError;
+
+    fn from_str(notation: &str) -> Result<Self, Self::Err> {
+        let mut parts = notation.split_whitespace();
+        let (size, rows) = match (parts.next(), parts.next()) {
+            (Some(size), Some(rows)) => (size, rows),
+            _ => return Err(NotationError::MissingRows),
+        };
+
+        let (width, height) = size
+            .split_once('x')
+            .and_then(|(width, height)| Some((width.parse().ok()?, height.parse().ok()?)))
+            .filter(|(width, height)| {
+                let range = MIN_BOARD_SIZE..=MAX_BOARD_SIZE;
+                range.contains(width) && range.contains(height)
+            })
+            .ok_or_else(|| NotationError::BadSize(size.to_string()))?;
+
+        let rows: Vec<&str> = rows.split('/').collect();
+        if rows.len() != usize::from(height) {
+            return Err(NotationError::WrongRowCount {
+                expected: height,
+                found: rows.len(),
+            });
+        }
+        let mut grid = Grid::new(width, height);
+        for (row, cells) in rows.into_iter().enumerate() {
+            let cells: Vec<&str> = cells.split(',').collect();
+            if cells.len() != usize::from(width) {
+                return Err(NotationError::WrongRowLength {
+                    row,
+                    expected: width,
+                    found: cells.len(),
+                });
+            }
+            // rows are written top down, y grows upwards
+            let y = height - 1 - row as u8;
+            for (x, cell) in cells.into_iter().enumerate() {
+                let value = match cell {
+                    "." => None,
+                    _ => Some(
+                        cell.parse()
+                            .ok()
+                            .filter(|value| *value > 0)
+                            .ok_or_else(|| NotationError::BadCell(cell.to_string()))?,
+                    ),
+                };
+                grid.set(Position { x: x as u8, y }, value);
+            }
+        }
+
+        let mut board = BoardNotation::new(grid);
+        for field in parts {
+            let bad_field = || NotationError::BadField(field.to_string());
+            match field.split_once('=') {
+                Some(("score", score)) => {
+                    board.score = Some(score.parse().map_err(|_| bad_field())?)
+                }
+                Some(("seed", seed)) => board.seed = Some(seed.parse().map_err(|_| bad_field())?),
+                _ => return Err(bad_field()),
+            }
+        }
+        Ok(board)
+    }
+}
+
+impl fmt::Display for BoardNotation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let grid = &self.grid;
+        let rows = (0..grid.height())
+            .rev()
+            .map(|y| {
+                (0..grid.width())
+                    .map(|x| match grid.get(Position { x, y }) {
+                        Some(value) => value.to_string(),
+                        None => ".".to_string(),
+                    })
+                    .join(",")
+            })
+            .join("/");
+        write!(f, "{}x{} {}", grid.width(), grid.height(), rows)?;
+        if let Some(score) = self.score {
+            write!(f, " score={}", score)?;
+        }
+        if let Some(seed) = self.seed {
+            write!(f, " seed={}", seed)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(notation: &str) -> Result<BoardNotation, NotationError> {
+        notation.parse()
+    }
+
+    #[test]
+    fn round_trips() {
+        for notation in &[
+            "4x4 2,.,.,./.,4,.,./.,.,.,./.,.,.,2 score=4 seed=42",
+            "2x3 .,./2048,./.,65536",
+            "3x2 2,4,8/16,32,64 seed=0",
+        ] {
+            assert_eq!(parse(notation).unwrap().to_string(), *notation);
+        }
+    }
+
+    #[test]
+    fn the_first_row_is_the_top_of_the_board() {
+        let board = parse("3x2 2,.,./.,.,4").unwrap();
+        assert_eq!(board.grid.get(Position { x: 0, y: 1 }), Some(2));
+        assert_eq!(board.grid.get(Position { x: 2, y: 0 }), Some(4));
+        assert_eq!(board.grid.tiles().count(), 2);
+    }
+
+    #[test]
+    fn rejects_broken_notation() {
+        assert_eq!(parse(""), Err(NotationError::MissingRows));
+        assert_eq!(parse("4x4"), Err(NotationError::MissingRows));
+        assert_eq!(
+            parse("9x9 ."),
+            Err(NotationError::BadSize("9x9".to_string()))
+        );
+        assert_eq!(parse("4 ."), Err(NotationError::BadSize("4".to_string())));
+        assert_eq!(
+            parse("2x2 .,."),
+            Err(NotationError::WrongRowCount {
+                expected: 2,
+                found: 1
+            })
+        );
+        assert_eq!(
+            parse("2x2 .,./.,.,."),
+            Err(NotationError::WrongRowLength {
+                row: 1,
+                expected: 2,
+                found: 3
+            })
+        );
+        assert_eq!(
+            parse("2x2 .,0/.,."),
+            Err(NotationError::BadCell("0".to_string()))
+        );
+        assert_eq!(
+            parse("2x2 .,x/.,."),
+            Err(NotationError::BadCell("x".to_string()))
+        );
+        assert_eq!(
+            parse("2x2 .,./.,. turn=3"),
+            Err(NotationError::BadField("turn=3".to_string()))
+        );
+        assert_eq!(
+            parse("2x2 .,./.,. score=lots"),
+            Err(NotationError::BadField("score=lots".to_string()))
+        );
+    }
+}
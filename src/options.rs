@@ -0,0 +1,337 @@
+use crate::components::{MAX_BOARD_SIZE, MIN_BOARD_SIZE};
+use crate::presets::{Preset, Presets};
+use crate::spawn::SpawnRules;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+pub const HELP: &str = "\
+usage: boxes [OPTIONS]
+
+Board and rules:
+    --preset NAME           start from a named set of rules, see --list-presets
+    --config PATH           read presets from PATH instead of presets.ron in
+                            the game's data directory
+    --list-presets          show the presets that can be used and exit
+    --size N | WxH          board size, from 2 to 8 on each side (default 4x4)
+    --seed N                seed for the tiles that are dealt
+    --target N              tile that wins the game (default 2048)
+    --undo-limit N          how many moves can be undone, 0 turns undo off
+    --spawn VALUE:WEIGHT,..  tiles that can spawn and how often (default 2:9,4:1)
+    --tiles-per-turn N      tiles spawned after every move (default 1)
+    --starting-tiles N      tiles dealt at the start of a game (default 2)
+
+Starting the game:
+    --continue              pick up the autosaved game
+    --position NOTATION     start from a board such as `4x4 2,.,.,./.,.,.,./.,.,.,./.,.,.,2`
+    --position-file PATH    start from the first position in a file
+    --replay PATH           watch a replay instead of playing
+
+Computer player:
+    --ai-depth N            most moves the computer looks ahead, up to 6, if
+                            it has the time (default 2)
+    --auto-ms N             time between the computer's moves when it plays
+                            by itself (default 200)
+
+Display:
+    --title TEXT            window title (default 2048)
+    --animation-ms N        how long tiles take to slide (default 100)
+    --theme dark|light      colours of the board (default dark)
+    --headless              don't open a window, print the starting board, or
+                            the end of the game when given --replay
+
+    --help                  show this message
+";
+
+/// The most moves the computer can be asked to look
+/// ahead, it runs out of time long before this anyway.
+pub const MAX_AI_DEPTH: u32 = 6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Theme {
+    Dark,
+    Light,
+}
+
+impl FromStr for Theme {
+    type Err = String;
+
+    fn from_str(theme: &str) -> Result<Self, Self::Err> {
+        match theme {
+            "dark" => Ok(Theme::Dark),
+            "light" => Ok(Theme::Light),
+            _ => Err(format!("unknown theme {:?}, expected dark or light", theme)),
+        }
+    }
+}
+
+/// How the game was launched. Anything left as `None`
+/// falls back to the game's defaults.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Options {
+    pub size: Option<(u8, u8)>,
+    pub seed: Option<u64>,
+    pub target: Option<u32>,
+    pub undo_limit: Option<usize>,
+    pub spawn_weights: Option<Vec<(u32, u32)>>,
+    pub tiles_per_turn: Option<usize>,
+    pub starting_tiles: Option<usize>,
+    pub preset: Option<String>,
+    pub config: Option<PathBuf>,
+    pub list_presets: bool,
+    pub continue_game: bool,
+    pub position: Option<String>,
+    pub position_file: Option<PathBuf>,
+    pub replay: Option<PathBuf>,
+    pub title: Option<String>,
+    pub animation_ms: Option<u64>,
+    pub theme: Option<Theme>,
+    pub ai_depth: Option<u32>,
+    pub auto_ms: Option<u64>,
+    pub headless: bool,
+    pub help: bool,
+}
+
+impl Options {
+    /// Parse everything after the program name.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut options = Options::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or_else(|| format!("{} needs a value", arg));
+            match arg.as_str() {
+                "--size" => options.size = Some(parse_board_size(&value()?)?),
+                "--seed" => options.seed = Some(parse_number(&arg, &value()?)?),
+                "--target" => options.target = Some(check_target(parse_number(&arg, &value()?)?)?),
+                "--undo-limit" => options.undo_limit = Some(parse_number(&arg, &value()?)?),
+                "--spawn" => options.spawn_weights = Some(parse_spawn_weights(&value()?)?),
+                "--tiles-per-turn" => options.tiles_per_turn = Some(parse_number(&arg, &value()?)?),
+                "--starting-tiles" => options.starting_tiles = Some(parse_number(&arg, &value()?)?),
+                "--preset" => options.preset = Some(value()?),
+                "--config" => options.config = Some(value()?.into()),
+                "--list-presets" => options.list_presets = true,
+                "--continue" => options.continue_game = true,
+                "--position" => options.position = Some(value()?),
+                "--position-file" => options.position_file = Some(value()?.into()),
+                "--replay" => options.replay = Some(value()?.into()),
+                "--title" => options.title = Some(value()?),
+                "--animation-ms" => options.animation_ms = Some(parse_number(&arg, &value()?)?),
+                "--theme" => options.theme = Some(value()?.parse()?),
+                "--ai-depth" => {
+                    let depth = parse_number(&arg, &value()?)?;
+                    if !(1..=MAX_AI_DEPTH).contains(&depth) {
+                        return Err(format!(
+                            "--ai-depth has to be between 1 and {}",
+                            MAX_AI_DEPTH
+                        ));
+                    }
+                    options.ai_depth = Some(depth);
+                }
+                "--auto-ms" => options.auto_ms = Some(parse_number(&arg, &value()?)?),
+                "--headless" => options.headless = true,
+                "--help" | "-h" => options.help = true,
+                _ => return Err(format!("unknown option {}", arg)),
+            }
+        }
+        Ok(options)
+    }
+
+    /// Apply the preset named by `--preset`, if any.
+    pub fn use_preset(&mut self, presets: &Presets) -> Result<(), String> {
+        let name = match &self.preset {
+            Some(name) => name.clone(),
+            None => return Ok(()),
+        };
+        let preset = presets.get(&name).ok_or_else(|| {
+            let names: Vec<&str> = presets.iter().map(|(name, _)| name).collect();
+            format!(
+                "unknown preset {:?}, expected one of {}",
+                name,
+                names.join(", ")
+            )
+        })?;
+        self.apply_preset(preset)
+            .map_err(|err| format!("preset {:?} is invalid: {}", name, err))
+    }
+
+    /// Fill in anything not given on the command line
+    /// from `preset`.
+    pub fn apply_preset(&mut self, preset: &Preset) -> Result<(), String> {
+        if let Some((width, height)) = preset.size {
+            check_board_size(width)?;
+            check_board_size(height)?;
+        }
+        if let Some(weights) = &preset.spawn_weights {
+            check_spawn_weights(weights)?;
+        }
+        if let Some(target) = preset.target {
+            check_target(target)?;
+        }
+        self.size = self.size.or(preset.size);
+        self.spawn_weights = self
+            .spawn_weights
+            .take()
+            .or_else(|| preset.spawn_weights.clone());
+        self.tiles_per_turn = self.tiles_per_turn.or(preset.tiles_per_turn);
+        self.starting_tiles = self.starting_tiles.or(preset.starting_tiles);
+        self.target = self.target.or(preset.target);
+        self.undo_limit = self.undo_limit.or(preset.undo_limit);
+        self.animation_ms = self.animation_ms.or(preset.animation_ms);
+        Ok(())
+    }
+
+    /// The default rules with any options applied.
+    pub fn spawn_rules(&self) -> SpawnRules {
+        let defaults = SpawnRules::default();
+        SpawnRules {
+            weights: self.spawn_weights.clone().unwrap_or(defaults.weights),
+            tiles_per_turn: self.tiles_per_turn.unwrap_or(defaults.tiles_per_turn),
+            starting_tiles: self.starting_tiles.unwrap_or(defaults.starting_tiles),
+        }
+    }
+}
+
+fn parse_number<T: FromStr>(name: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("{} expects a number, got {}", name, value))
+}
+
+/// Either a single number for a square board, or
+/// `WIDTHxHEIGHT`.
+pub fn parse_board_size(size: &str) -> Result<(u8, u8), String> {
+    let parse = |length: &str| -> Result<u8, String> {
+        length
+            .parse()
+            .map_err(|_| format!("--size expects N or WIDTHxHEIGHT, got {}", size))
+            .and_then(check_board_size)
+    };
+    match size.split_once('x') {
+        Some((width, height)) => Ok((parse(width)?, parse(height)?)),
+        None => Ok((parse(size)?, parse(size)?)),
+    }
+}
+
+fn check_board_size(length: u8) -> Result<u8, String> {
+    if (MIN_BOARD_SIZE..=MAX_BOARD_SIZE).contains(&length) {
+        Ok(length)
+    } else {
+        Err(format!(
+            "board sides must be between {} and {}, got {}",
+            MIN_BOARD_SIZE, MAX_BOARD_SIZE, length
+        ))
+    }
+}
+
+/// The `VALUE:WEIGHT,...` list used by `--spawn`, the
+/// same way `rules_key` writes it.
+pub fn parse_spawn_weights(weights: &str) -> Result<Vec<(u32, u32)>, String> {
+    let invalid = || format!("--spawn expects VALUE:WEIGHT,..., got {}", weights);
+    let weights = weights
+        .split(',')
+        .map(|pair| {
+            let (value, weight) = pair.split_once(':').ok_or_else(invalid)?;
+            Ok((
+                value.parse().map_err(|_| invalid())?,
+                weight.parse().map_err(|_| invalid())?,
+            ))
+        })
+        .collect::<Result<Vec<(u32, u32)>, String>>()?;
+    check_spawn_weights(&weights)?;
+    Ok(weights)
+}
+
+fn check_target(target: u32) -> Result<u32, String> {
+    if target == 0 {
+        return Err("the target tile can't be 0".to_string());
+    }
+    Ok(target)
+}
+
+fn check_spawn_weights(weights: &[(u32, u32)]) -> Result<(), String> {
+    if weights.iter().any(|(value, _)| *value == 0) {
+        return Err("spawned tiles can't be 0".to_string());
+    }
+    if weights.iter().all(|(_, weight)| *weight == 0) {
+        return Err("spawn weights need at least one that isn't 0".to_string());
+    }
+    // the odds of every tile are worked out from the total
+    let total = weights
+        .iter()
+        .try_fold(0u32, |total, (_, weight)| total.checked_add(*weight));
+    if total.is_none() {
+        return Err(format!("spawn weights can add up to at most {}", u32::MAX));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        Options::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn parses_options() {
+        let options = parse(&["--size", "5x3", "--spawn", "2:3,4:1", "--ai-depth", "6"]).unwrap();
+        assert_eq!(options.size, Some((5, 3)));
+        assert_eq!(options.spawn_weights, Some(vec![(2, 3), (4, 1)]));
+        assert_eq!(options.ai_depth, Some(6));
+        assert_eq!(parse(&["--size", "6"]).unwrap().size, Some((6, 6)));
+    }
+
+    #[test]
+    fn rejects_bad_options() {
+        for args in [
+            &["--seed"][..],
+            &["--size", "9"],
+            &["--size", "3x"],
+            &["--size", "1x4"],
+            &["--spawn", "2:0"],
+            &["--spawn", "0:1"],
+            &["--spawn", "2"],
+            &["--spawn", "2:4294967295,4:1"],
+            &["--target", "0"],
+            &["--ai-depth", "0"],
+            &["--ai-depth", "7"],
+            &["--theme", "blue"],
+            &["--sise", "4"],
+        ] {
+            assert!(parse(args).is_err(), "{:?} should be rejected", args);
+        }
+        assert_eq!(parse(&["--seed"]), Err("--seed needs a value".to_string()));
+    }
+
+    #[test]
+    fn the_command_line_wins_over_a_preset() {
+        let mut options = parse(&["--preset", "5x5 hard", "--size", "3x3"]).unwrap();
+        options.use_preset(&Presets::builtin()).unwrap();
+        assert_eq!(options.size, Some((3, 3)));
+        assert_eq!(options.spawn_weights, Some(vec![(2, 3), (4, 1)]));
+        assert_eq!(options.undo_limit, Some(0));
+
+        let mut options = parse(&["--preset", "missing"]).unwrap();
+        assert!(options.use_preset(&Presets::builtin()).is_err());
+    }
+
+    #[test]
+    fn rejects_bad_presets() {
+        for preset in [
+            Preset {
+                size: Some((4, 9)),
+                ..Default::default()
+            },
+            Preset {
+                spawn_weights: Some(vec![(2, u32::MAX), (4, 1)]),
+                ..Default::default()
+            },
+            Preset {
+                target: Some(0),
+                ..Default::default()
+            },
+        ] {
+            assert!(Options::default().apply_preset(&preset).is_err());
+        }
+    }
+}
@@ -0,0 +1,202 @@
+use crate::{move_blocks, restore_snapshot, spawn_block, Materials};
+use bevy::prelude::*;
+use boxes::components::{Block, BlockText, Board, Game, Position};
+use boxes::replay::{Replay, ReplayError};
+use boxes::rng::GameRng;
+use boxes::snapshot::Snapshot;
+use std::collections::HashMap;
+use std::time::Duration;
+
+const PLAYBACK_KEYS: [(KeyCode, PlaybackEvent); 5] = [
+    (KeyCode::Space, PlaybackEvent::PlayPause),
+    (KeyCode::Left, PlaybackEvent::StepBack),
+    (KeyCode::Right, PlaybackEvent::StepForward),
+    (KeyCode::Down, PlaybackEvent::Slower),
+    (KeyCode::Up, PlaybackEvent::Faster),
+];
+// between moves of a replay that didn't keep times
+const MOVE_DELAY: Duration = Duration::from_millis(250);
+// recorded pauses are squeezed into this range so nobody
+// sits through a player's coffee break
+const MIN_MOVE_DELAY: Duration = Duration::from_millis(50);
+const MAX_MOVE_DELAY: Duration = Duration::from_secs(2);
+const MIN_SPEED: f32 = 0.25;
+const MAX_SPEED: f32 = 16.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlaybackEvent {
+    PlayPause,
+    StepBack,
+    StepForward,
+    Slower,
+    Faster,
+}
+
+/// The replay being watched in `RunState::Replay`.
+pub struct Playback {
+    pub replay: Replay,
+    // every position of the game, one more than there
+    // are moves
+    snapshots: Vec<Snapshot>,
+    // index into `snapshots` of what's on the board
+    position: usize,
+    pub playing: bool,
+    pub speed: f32,
+    // time spent on the current position so far, already
+    // scaled by `speed`
+    waited: Duration,
+}
+
+impl Playback {
+    pub fn new(replay: Replay) -> Result<Self, ReplayError> {
+        Ok(Playback {
+            snapshots: replay.snapshots()?,
+            replay,
+            position: 0,
+            playing: true,
+            speed: 1.0,
+            waited: Duration::ZERO,
+        })
+    }
+
+    /// How many moves have been played so far.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn total_moves(&self) -> usize {
+        self.replay.moves.len()
+    }
+
+    // how long the player took over the next move
+    fn delay(&self) -> Duration {
+        let times = match &self.replay.times {
+            Some(times) => times,
+            None => return MOVE_DELAY,
+        };
+        let previous = match self.position {
+            0 => 0,
+            position => times.get(position - 1).copied().unwrap_or(0),
+        };
+        match times.get(self.position) {
+            Some(time) => Duration::from_millis(time.saturating_sub(previous))
+                .max(MIN_MOVE_DELAY)
+                .min(MAX_MOVE_DELAY),
+            None => MOVE_DELAY,
+        }
+    }
+}
+
+pub fn start_playback(
+    mut commands: Commands,
+    materials: Res<Materials>,
+    asset_server: Res<AssetServer>,
+    query_board: Query<&Board>,
+    blocks: Query<Entity, With<Block>>,
+    mut playback: ResMut<Playback>,
+    mut game: ResMut<Game>,
+    mut game_rng: ResMut<GameRng>,
+) {
+    let board = query_board.single().expect("expect there to be a board");
+    for entity in blocks.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    playback.position = 0;
+    playback.waited = Duration::ZERO;
+    restore_snapshot(
+        &mut commands,
+        &materials,
+        &asset_server,
+        board,
+        &playback.snapshots[0],
+        &mut game,
+        &mut game_rng,
+    );
+}
+
+pub fn advance_playback(
+    mut commands: Commands,
+    keyboard_input: Res<Input<KeyCode>>,
+    time: Res<Time>,
+    mut playback_reader: EventReader<PlaybackEvent>,
+    mut playback: ResMut<Playback>,
+    materials: Res<Materials>,
+    asset_server: Res<AssetServer>,
+    query_board: Query<&Board>,
+    mut texts: Query<&mut Text, With<BlockText>>,
+    mut blocks: Query<(Entity, &mut Position, &mut Block, &Children)>,
+    mut game: ResMut<Game>,
+    mut game_rng: ResMut<GameRng>,
+) {
+    let board = query_board.single().expect("expect there to be a board");
+
+    // only one step a frame, blocks merged away by a
+    // step are only gone once commands are applied
+    let mut step = None;
+    let pressed = PLAYBACK_KEYS
+        .iter()
+        .filter(|(key, _)| keyboard_input.just_pressed(*key))
+        .map(|(_, event)| *event);
+    for event in pressed.chain(playback_reader.iter().copied()) {
+        match event {
+            PlaybackEvent::PlayPause => playback.playing = !playback.playing,
+            PlaybackEvent::StepBack | PlaybackEvent::StepForward => {
+                playback.playing = false;
+                step = Some(event);
+            }
+            PlaybackEvent::Slower => playback.speed = (playback.speed / 2.0).max(MIN_SPEED),
+            PlaybackEvent::Faster => playback.speed = (playback.speed * 2.0).min(MAX_SPEED),
+        }
+    }
+    if playback.playing && step.is_none() {
+        let speed = playback.speed;
+        playback.waited += time.delta().mul_f32(speed);
+        if playback.waited >= playback.delay() {
+            step = Some(PlaybackEvent::StepForward);
+        }
+    }
+
+    match step {
+        Some(PlaybackEvent::StepForward) if playback.position < playback.total_moves() => {
+            let before = &playback.snapshots[playback.position];
+            let after = &playback.snapshots[playback.position + 1];
+            let outcome = before.grid.shift(playback.replay.moves[playback.position]);
+            let entities: HashMap<Position, Entity> = blocks
+                .iter_mut()
+                .map(|(entity, position, _, _)| (*position, entity))
+                .collect();
+            move_blocks(&mut commands, &mut texts, &mut blocks, &entities, &outcome);
+            // whatever is new after the move was spawned
+            for (pos, value) in after.grid.tiles() {
+                if outcome.grid.get(pos).is_none() {
+                    spawn_block(&mut commands, &materials, &asset_server, board, pos, value);
+                }
+            }
+            game.score = after.score;
+            game.moves = after.moves;
+            playback.position += 1;
+        }
+        Some(PlaybackEvent::StepBack) if playback.position > 0 => {
+            for (entity, _, _, _) in blocks.iter_mut() {
+                commands.entity(entity).despawn_recursive();
+            }
+            playback.position -= 1;
+            restore_snapshot(
+                &mut commands,
+                &materials,
+                &asset_server,
+                board,
+                &playback.snapshots[playback.position],
+                &mut game,
+                &mut game_rng,
+            );
+        }
+        _ => {}
+    }
+    if step.is_some() {
+        playback.waited = Duration::ZERO;
+    }
+    if playback.position == playback.total_moves() {
+        playback.playing = false;
+    }
+}
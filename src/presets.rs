@@ -0,0 +1,182 @@
+use crate::storage::{self, StorageError};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// A named set of rules. Anything left out keeps the
+/// game's default, and options given on the command line
+/// win over the preset.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Preset {
+    pub size: Option<(u8, u8)>,
+    pub spawn_weights: Option<Vec<(u32, u32)>>,
+    pub tiles_per_turn: Option<usize>,
+    pub starting_tiles: Option<usize>,
+    pub target: Option<u32>,
+    pub undo_limit: Option<usize>,
+    /// How long a tile takes to slide, in milliseconds.
+    pub animation_ms: Option<u64>,
+}
+
+impl fmt::Display for Preset {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut rules = Vec::new();
+        if let Some((width, height)) = self.size {
+            rules.push(format!("{}x{}", width, height));
+        }
+        if let Some(weights) = &self.spawn_weights {
+            let weights = weights
+                .iter()
+                .map(|(value, weight)| format!("{}:{}", value, weight))
+                .join(",");
+            rules.push(format!("spawn {}", weights));
+        }
+        if let Some(tiles) = self.tiles_per_turn {
+            rules.push(format!("{} tiles per turn", tiles));
+        }
+        if let Some(tiles) = self.starting_tiles {
+            rules.push(format!("{} starting tiles", tiles));
+        }
+        if let Some(target) = self.target {
+            rules.push(format!("target {}", target));
+        }
+        if let Some(undo_limit) = self.undo_limit {
+            rules.push(format!("undo limit {}", undo_limit));
+        }
+        if let Some(animation_ms) = self.animation_ms {
+            rules.push(format!("{}ms animations", animation_ms));
+        }
+        if rules.is_empty() {
+            write!(f, "default rules")
+        } else {
+            write!(f, "{}", rules.join(", "))
+        }
+    }
+}
+
+/// Presets by name, the built in ones plus any from the
+/// presets file, which looks like
+///
+/// ```text
+/// #![enable(implicit_some)]
+/// {
+///     "5x5 hard": (size: (5, 5), spawn_weights: [(2, 3), (4, 1)], undo_limit: 0),
+/// }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Presets {
+    presets: BTreeMap<String, Preset>,
+}
+
+impl Presets {
+    pub fn path() -> PathBuf {
+        storage::data_dir().join("presets.ron")
+    }
+
+    pub fn builtin() -> Self {
+        let mut presets = BTreeMap::new();
+        presets.insert("classic".to_string(), Preset::default());
+        presets.insert(
+            "5x5 hard".to_string(),
+            Preset {
+                size: Some((5, 5)),
+                spawn_weights: Some(vec![(2, 3), (4, 1)]),
+                undo_limit: Some(0),
+                ..Default::default()
+            },
+        );
+        presets.insert(
+            "speedrun".to_string(),
+            Preset {
+                undo_limit: Some(0),
+                animation_ms: Some(30),
+                ..Default::default()
+            },
+        );
+        Presets { presets }
+    }
+
+    /// The built in presets, overridden by the ones in
+    /// `path`. Without a path the default presets file is
+    /// read if there is one.
+    pub fn load(path: Option<&Path>) -> Result<Self, StorageError> {
+        let mut presets = Presets::builtin();
+        let default_path = Presets::path();
+        let contents = match fs::read_to_string(path.unwrap_or(&default_path)) {
+            Ok(contents) => contents,
+            Err(err) if path.is_none() && err.kind() == io::ErrorKind::NotFound => {
+                return Ok(presets)
+            }
+            Err(err) => return Err(err.into()),
+        };
+        let from_file: BTreeMap<String, Preset> = ron::from_str(&contents)?;
+        presets.presets.extend(from_file);
+        Ok(presets)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Preset> {
+        self.presets.get(name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Preset)> {
+        self.presets
+            .iter()
+            .map(|(name, preset)| (name.as_str(), preset))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a presets file of `contents`, loaded and then removed
+    fn load(name: &str, contents: &str) -> Result<Presets, StorageError> {
+        let path =
+            std::env::temp_dir().join(format!("boxes-presets-{}-{}.ron", name, std::process::id()));
+        fs::write(&path, contents).unwrap();
+        let presets = Presets::load(Some(&path));
+        fs::remove_file(&path).unwrap();
+        presets
+    }
+
+    #[test]
+    fn the_file_overrides_builtin_presets() {
+        let presets = load(
+            "override",
+            r#"#![enable(implicit_some)]
+            {
+                "speedrun": (animation_ms: 10),
+                "tiny": (size: (2, 2)),
+            }"#,
+        )
+        .unwrap();
+        let speedrun = presets.get("speedrun").unwrap();
+        assert_eq!(speedrun.animation_ms, Some(10));
+        // replaced whole, not merged with the built in one
+        assert_eq!(speedrun.undo_limit, None);
+        assert_eq!(presets.get("tiny").unwrap().size, Some((2, 2)));
+        assert_eq!(presets.get("classic"), Presets::builtin().get("classic"));
+    }
+
+    #[test]
+    fn rejects_unknown_fields() {
+        assert!(matches!(
+            load("unknown", r#"{ "typo": (sise: Some((5, 5))) }"#),
+            Err(StorageError::Format(_))
+        ));
+    }
+
+    #[test]
+    fn a_missing_config_is_an_error() {
+        let path = std::env::temp_dir().join("boxes-presets-that-dont-exist.ron");
+        assert!(matches!(
+            Presets::load(Some(&path)),
+            Err(StorageError::Io(err)) if err.kind() == io::ErrorKind::NotFound
+        ));
+    }
+}
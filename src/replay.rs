@@ -0,0 +1,275 @@
+use crate::components::{MAX_BOARD_SIZE, MIN_BOARD_SIZE};
+use crate::grid::{Direction, Grid};
+use crate::rng::GameRng;
+use crate::snapshot::Snapshot;
+use crate::spawn::SpawnRules;
+use crate::storage::{self, StorageError};
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Bumped whenever `Replay` changes shape.
+pub const REPLAY_VERSION: u32 = 1;
+
+/// A game written down as the moves that were made. The
+/// seed and rules deal the same tiles again, so the moves
+/// are all it takes to play the game back.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Replay {
+    pub version: u32,
+    pub seed: u64,
+    pub width: u8,
+    pub height: u8,
+    pub rules: SpawnRules,
+    /// Where play began when it wasn't a freshly dealt
+    /// board, e.g. a game loaded from a save.
+    #[serde(default)]
+    pub start: Option<Snapshot>,
+    /// Written as one letter per move, e.g. `"LLUR"`.
+    #[serde(with = "direction_letters")]
+    pub moves: Vec<Direction>,
+    /// Milliseconds from the start of the game to each move.
+    #[serde(default)]
+    pub times: Option<Vec<u64>>,
+    /// How the game ended according to whoever recorded it.
+    #[serde(default)]
+    pub result: Option<ReplayResult>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReplayResult {
+    pub score: u32,
+    pub max_tile: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplayError {
+    BoardSize { width: u8, height: u8 },
+    // no tile could ever be picked
+    NoSpawnWeights,
+    // the starting position is for a different board
+    StartMismatch,
+    // the move at `index` doesn't move anything, so it
+    // can't have been played
+    IllegalMove { index: usize, direction: Direction },
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReplayError::BoardSize { width, height } => {
+                write!(f, "{}x{} is not a supported board size", width, height)
+            }
+            ReplayError::NoSpawnWeights => write!(f, "the spawn rules have no non-zero weight"),
+            ReplayError::StartMismatch => {
+                write!(f, "the starting position doesn't fit the board")
+            }
+            ReplayError::IllegalMove { index, direction } => {
+                write!(
+                    f,
+                    "move {} ({:?}) doesn't move anything",
+                    index + 1,
+                    direction
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+impl Replay {
+    pub fn new(
+        seed: u64,
+        width: u8,
+        height: u8,
+        rules: SpawnRules,
+        start: Option<Snapshot>,
+    ) -> Self {
+        Replay {
+            version: REPLAY_VERSION,
+            seed,
+            width,
+            height,
+            rules,
+            start,
+            moves: vec![],
+            times: Some(vec![]),
+            result: None,
+        }
+    }
+
+    pub fn dir() -> PathBuf {
+        storage::data_dir().join("replays")
+    }
+
+    /// The game's move count before the first move here.
+    pub fn start_moves(&self) -> u32 {
+        self.start.as_ref().map_or(0, |start| start.moves)
+    }
+
+    /// Record a move. `time` is dropped if this replay
+    /// doesn't keep times.
+    pub fn push(&mut self, direction: Direction, time: Duration) {
+        self.moves.push(direction);
+        if let Some(times) = &mut self.times {
+            times.push(time.as_millis() as u64);
+        }
+    }
+
+    /// Forget everything after the first `len` moves, e.g.
+    /// when they have been undone.
+    pub fn truncate(&mut self, len: usize) {
+        self.moves.truncate(len);
+        if let Some(times) = &mut self.times {
+            times.truncate(len);
+        }
+    }
+
+    /// The game before the first move.
+    pub fn initial(&self) -> Result<Snapshot, ReplayError> {
+        let size = MIN_BOARD_SIZE..=MAX_BOARD_SIZE;
+        if !size.contains(&self.width) || !size.contains(&self.height) {
+            return Err(ReplayError::BoardSize {
+                width: self.width,
+                height: self.height,
+            });
+        }
+        if self.rules.weights.iter().all(|(_, weight)| *weight == 0) {
+            return Err(ReplayError::NoSpawnWeights);
+        }
+        match &self.start {
+            Some(start) => {
+                if (start.grid.width(), start.grid.height()) != (self.width, self.height) {
+                    return Err(ReplayError::StartMismatch);
+                }
+                Ok(start.clone())
+            }
+            None => {
+                let mut rng = GameRng::new(self.seed);
+                let mut grid = Grid::new(self.width, self.height);
+                self.rules
+                    .spawn(&mut grid, self.rules.starting_tiles, &mut rng);
+                Ok(Snapshot {
+                    grid,
+                    score: 0,
+                    moves: 0,
+                    rng: rng.state(),
+                })
+            }
+        }
+    }
+
+    /// Whether playing this replay through ends at
+    /// `snapshot`, e.g. to check that a replay saved with
+    /// a game still belongs to it.
+    pub fn reaches(&self, snapshot: &Snapshot) -> bool {
+        self.snapshots()
+            .is_ok_and(|snapshots| snapshots.last() == Some(snapshot))
+    }
+
+    /// Play the whole game through the same rules as live
+    /// play, returning every position it passed through
+    /// starting with `initial`.
+    pub fn snapshots(&self) -> Result<Vec<Snapshot>, ReplayError> {
+        let mut snapshots = vec![self.initial()?];
+        for (index, direction) in self.moves.iter().copied().enumerate() {
+            let before = snapshots.last().expect("there to be an initial snapshot");
+            let turn = before
+                .play(direction, &self.rules)
+                .ok_or(ReplayError::IllegalMove { index, direction })?;
+            snapshots.push(turn.snapshot);
+        }
+        Ok(snapshots)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, StorageError> {
+        storage::load_versioned(path, REPLAY_VERSION)
+    }
+
+    // one line per field, long games would otherwise
+    // spread the times over thousands of lines
+    pub fn save(&self, path: &Path) -> Result<(), StorageError> {
+        storage::save_with(path, self, PrettyConfig::new().with_depth_limit(1))
+    }
+}
+
+mod direction_letters {
+    use crate::grid::Direction;
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(moves: &[Direction], serializer: S) -> Result<S::Ok, S::Error> {
+        let letters: String = moves.iter().map(|direction| direction.letter()).collect();
+        serializer.serialize_str(&letters)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<Direction>, D::Error> {
+        String::deserialize(deserializer)?
+            .chars()
+            .map(|letter| {
+                Direction::from_letter(letter)
+                    .ok_or_else(|| D::Error::custom(format!("{:?} is not a move", letter)))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn replay() -> Replay {
+        let mut replay = Replay::new(7, 4, 4, SpawnRules::default(), None);
+        for (time, direction) in Direction::ALL.iter().enumerate() {
+            replay.push(*direction, Duration::from_millis(time as u64 * 100));
+        }
+        replay
+    }
+
+    #[test]
+    fn moves_are_written_as_letters() {
+        let replay = replay();
+        let written = ron::to_string(&replay).unwrap();
+        assert!(written.contains("moves:\"LRUD\""), "{}", written);
+        assert_eq!(ron::from_str::<Replay>(&written).unwrap(), replay);
+
+        let bad_letter = written.replace("LRUD", "LRXD");
+        assert!(ron::from_str::<Replay>(&bad_letter).is_err());
+    }
+
+    #[test]
+    fn truncate_keeps_times_with_their_moves() {
+        let mut replay = replay();
+        replay.truncate(2);
+        assert_eq!(replay.moves, vec![Direction::Left, Direction::Right]);
+        assert_eq!(replay.times, Some(vec![0, 100]));
+
+        replay.times = None;
+        replay.truncate(1);
+        replay.push(Direction::Up, Duration::from_millis(5));
+        assert_eq!(replay.moves, vec![Direction::Left, Direction::Up]);
+        assert_eq!(replay.times, None);
+    }
+
+    #[test]
+    fn rejects_other_versions() {
+        let path = std::env::temp_dir().join(format!("boxes-replay-{}.ron", std::process::id()));
+        let mut replay = replay();
+        replay.save(&path).unwrap();
+        assert_eq!(Replay::load(&path).unwrap(), replay);
+
+        replay.version = REPLAY_VERSION + 1;
+        replay.save(&path).unwrap();
+        let loaded = Replay::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(
+            loaded,
+            Err(StorageError::UnsupportedVersion(version)) if version == REPLAY_VERSION + 1
+        ));
+    }
+}
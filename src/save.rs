@@ -0,0 +1,46 @@
+use crate::components::RunState;
+use crate::replay::Replay;
+use crate::snapshot::Snapshot;
+use crate::storage::{self, StorageError};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Bumped whenever `SavedGame` changes shape.
+pub const SAVE_VERSION: u32 = 1;
+
+/// An in-progress game, as written to disk.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SavedGame {
+    pub version: u32,
+    pub snapshot: Snapshot,
+    pub endless: bool,
+    pub state: RunState,
+    /// The game so far, so a replay of it can carry on
+    /// once the game is continued.
+    #[serde(default)]
+    pub replay: Option<Replay>,
+}
+
+impl SavedGame {
+    pub fn new(snapshot: Snapshot, endless: bool, state: RunState) -> Self {
+        SavedGame {
+            version: SAVE_VERSION,
+            snapshot,
+            endless,
+            state,
+            replay: None,
+        }
+    }
+
+    pub fn autosave_path() -> PathBuf {
+        storage::data_dir().join("autosave.ron")
+    }
+
+    pub fn load(path: &Path) -> Result<Self, StorageError> {
+        storage::load_versioned(path, SAVE_VERSION)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), StorageError> {
+        storage::save(path, self)
+    }
+}
@@ -0,0 +1,96 @@
+use crate::spawn::SpawnRules;
+use crate::storage::{self, StorageError};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// Best scores, kept apart for every board size and set
+/// of spawn rules since they aren't comparable.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BestScores {
+    scores: BTreeMap<String, u32>,
+}
+
+impl BestScores {
+    pub fn path() -> PathBuf {
+        storage::data_dir().join("best_scores.ron")
+    }
+
+    pub fn load() -> Result<Self, StorageError> {
+        match storage::load(&Self::path()) {
+            // no file just means nobody has played yet
+            Err(StorageError::Io(err)) if err.kind() == std::io::ErrorKind::NotFound => {
+                Ok(Self::default())
+            }
+            result => result,
+        }
+    }
+
+    pub fn save(&self) -> Result<(), StorageError> {
+        storage::save(&Self::path(), self)
+    }
+
+    pub fn get(&self, key: &str) -> u32 {
+        self.scores.get(key).copied().unwrap_or(0)
+    }
+
+    /// Returns whether `score` beat the stored best.
+    pub fn update(&mut self, key: &str, score: u32) -> bool {
+        if score <= self.get(key) {
+            return false;
+        }
+        self.scores.insert(key.to_string(), score);
+        true
+    }
+
+    /// `update`, saving the scores when `score` is a new
+    /// best.
+    pub fn update_and_save(&mut self, key: &str, score: u32) -> Result<bool, StorageError> {
+        if !self.update(key, score) {
+            return Ok(false);
+        }
+        self.save()?;
+        Ok(true)
+    }
+}
+
+/// Identifies a board size and rule set, e.g.
+/// `4x4 2:9,4:1 per_turn=1 start=2`.
+pub fn rules_key(width: u8, height: u8, rules: &SpawnRules) -> String {
+    let weights = rules
+        .weights
+        .iter()
+        .map(|(value, weight)| format!("{}:{}", value, weight))
+        .collect::<Vec<_>>()
+        .join(",");
+    format!(
+        "{}x{} {} per_turn={} start={}",
+        width, height, weights, rules.tiles_per_turn, rules.starting_tiles
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_a_higher_score_is_kept() {
+        let mut scores = BestScores::default();
+        assert_eq!(scores.get("4x4"), 0);
+        assert!(scores.update("4x4", 100));
+        assert!(!scores.update("4x4", 100));
+        assert!(!scores.update("4x4", 40));
+        assert_eq!(scores.get("4x4"), 100);
+        assert!(scores.update("4x4", 120));
+        assert_eq!(scores.get("4x4"), 120);
+        // every rule set has its own best
+        assert_eq!(scores.get("5x5"), 0);
+    }
+
+    #[test]
+    fn keys_tell_rule_sets_apart() {
+        let rules = SpawnRules::default();
+        assert_eq!(rules_key(4, 4, &rules), "4x4 2:9,4:1 per_turn=1 start=2");
+        assert_ne!(rules_key(4, 4, &rules), rules_key(4, 5, &rules));
+    }
+}
@@ -0,0 +1,130 @@
+use crate::save::SavedGame;
+use crate::storage::{self, StorageError};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+pub const MAX_SLOT_NAME_LENGTH: usize = 24;
+
+/// What the slot list shows without loading the game.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SlotInfo {
+    pub name: String,
+    pub saved_at: SystemTime,
+    pub score: u32,
+    pub max_tile: u32,
+}
+
+/// Named saves, each one a `SavedGame` file in `dir`.
+#[derive(Debug, Clone)]
+pub struct SaveSlots {
+    dir: PathBuf,
+}
+
+impl Default for SaveSlots {
+    fn default() -> Self {
+        SaveSlots::new(storage::data_dir().join("slots"))
+    }
+}
+
+// names become file names, so keep them boring
+pub fn is_valid_slot_name(name: &str) -> bool {
+    !name.trim().is_empty()
+        && name.len() <= MAX_SLOT_NAME_LENGTH
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == ' ')
+}
+
+impl SaveSlots {
+    pub fn new(dir: PathBuf) -> Self {
+        SaveSlots { dir }
+    }
+
+    pub fn path(&self, name: &str) -> Result<PathBuf, StorageError> {
+        if !is_valid_slot_name(name) {
+            return Err(StorageError::InvalidName(name.to_string()));
+        }
+        Ok(self.dir.join(format!("{}.ron", name.trim())))
+    }
+
+    /// Every readable slot, most recent first. Files that
+    /// can't be read are left out rather than failing the
+    /// whole list.
+    pub fn list(&self) -> Result<Vec<SlotInfo>, StorageError> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(err) => return Err(err.into()),
+        };
+        let mut slots = vec![];
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("ron") {
+                continue;
+            }
+            let name = match path.file_stem().and_then(|stem| stem.to_str()) {
+                Some(name) => name.to_string(),
+                None => continue,
+            };
+            let (game, saved_at) = match (
+                SavedGame::load(&path),
+                fs::metadata(&path).and_then(|meta| meta.modified()),
+            ) {
+                (Ok(game), Ok(saved_at)) => (game, saved_at),
+                _ => continue,
+            };
+            slots.push(SlotInfo {
+                name,
+                saved_at,
+                score: game.snapshot.score,
+                max_tile: game.snapshot.grid.max_tile().unwrap_or(0),
+            });
+        }
+        slots.sort_by_key(|slot| std::cmp::Reverse(slot.saved_at));
+        Ok(slots)
+    }
+
+    pub fn save(&self, name: &str, game: &SavedGame) -> Result<(), StorageError> {
+        game.save(&self.path(name)?)
+    }
+
+    pub fn load(&self, name: &str) -> Result<SavedGame, StorageError> {
+        SavedGame::load(&self.path(name)?)
+    }
+
+    pub fn delete(&self, name: &str) -> Result<(), StorageError> {
+        fs::remove_file(self.path(name)?)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slot_names_stay_in_the_slot_dir() {
+        for name in ["quick save", "run_2", "a-b"] {
+            assert!(is_valid_slot_name(name), "{:?}", name);
+        }
+        let too_long = "x".repeat(MAX_SLOT_NAME_LENGTH + 1);
+        for name in ["", "   ", "../x", "a/b", "a\\b", ".", "slot.ron", &too_long] {
+            assert!(!is_valid_slot_name(name), "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn slot_paths_are_checked() {
+        let slots = SaveSlots::new(PathBuf::from("slots"));
+        assert_eq!(
+            slots.path(" quick ").unwrap(),
+            PathBuf::from("slots").join("quick.ron")
+        );
+        assert!(matches!(
+            slots.path("../x"),
+            Err(StorageError::InvalidName(_))
+        ));
+    }
+}
@@ -0,0 +1,130 @@
+use crate::components::{Position, RunState, WinTarget};
+use crate::grid::{Direction, Grid, MoveOutcome};
+use crate::rng::{GameRng, RngState};
+use crate::spawn::SpawnRules;
+use serde::{Deserialize, Serialize};
+
+/// Everything needed to put a game back the way it was.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub grid: Grid,
+    pub score: u32,
+    pub moves: u32,
+    pub rng: RngState,
+}
+
+/// One move and the tiles spawned after it, see
+/// `Snapshot::play`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Turn {
+    /// The game once the new tiles are in.
+    pub snapshot: Snapshot,
+    /// The move on its own, before anything spawned.
+    pub outcome: MoveOutcome,
+    pub spawned: Vec<(Position, u32)>,
+}
+
+impl Snapshot {
+    /// Play a turn by the game's rules: the move, its
+    /// score, then the tiles spawned from the rng where
+    /// this snapshot left it. `None` if the move doesn't
+    /// change anything, which doesn't count as a turn.
+    ///
+    /// Every frontend and replays go through here, so a
+    /// game plays out the same wherever it's played.
+    pub fn play(&self, direction: Direction, rules: &SpawnRules) -> Option<Turn> {
+        let outcome = self.grid.shift(direction);
+        if !outcome.moved() {
+            return None;
+        }
+        let mut rng = GameRng::new(self.rng.seed);
+        rng.restore(self.rng);
+        let mut grid = outcome.grid.clone();
+        let spawned = rules.spawn(&mut grid, rules.tiles_per_turn, &mut rng);
+        Some(Turn {
+            snapshot: Snapshot {
+                grid,
+                score: self.score + outcome.score,
+                moves: self.moves + 1,
+                rng: rng.state(),
+            },
+            outcome,
+            spawned,
+        })
+    }
+}
+
+impl Turn {
+    /// Where the game stands after this turn. Winning
+    /// takes a merged tile of at least `target`, unless the
+    /// player already won and kept going, and beats the
+    /// board filling up on the same turn.
+    pub fn state(&self, target: &WinTarget, endless: bool) -> RunState {
+        if !endless && target.reached_by(&self.outcome) {
+            RunState::Won
+        } else if !self.snapshot.grid.has_legal_move() {
+            RunState::GameOver
+        } else {
+            RunState::Playing
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(tiles: &[(u8, u8, u32)]) -> Snapshot {
+        let grid = Grid::from_tiles(
+            4,
+            4,
+            tiles
+                .iter()
+                .map(|&(x, y, value)| (Position { x, y }, value)),
+        );
+        Snapshot {
+            grid,
+            score: 10,
+            moves: 3,
+            rng: GameRng::new(1).state(),
+        }
+    }
+
+    #[test]
+    fn a_turn_moves_scores_and_spawns() {
+        let rules = SpawnRules::default();
+        let before = snapshot(&[(2, 0, 4), (3, 0, 4)]);
+        let turn = before.play(Direction::Left, &rules).unwrap();
+        assert_eq!(turn.snapshot.score, 18);
+        assert_eq!(turn.snapshot.moves, 4);
+        assert_eq!(turn.spawned.len(), rules.tiles_per_turn);
+        assert_eq!(turn.snapshot.grid.tiles().count(), 2);
+        assert_ne!(turn.snapshot.rng, before.rng);
+        // the same rng state deals the same tiles
+        assert_eq!(before.play(Direction::Left, &rules), Some(turn));
+
+        assert_eq!(
+            before.play(Direction::Right, &rules).unwrap().spawned.len(),
+            1
+        );
+        let stuck = snapshot(&[(0, 0, 2)]);
+        assert_eq!(stuck.play(Direction::Left, &rules), None);
+    }
+
+    #[test]
+    fn only_a_merged_tile_wins() {
+        let rules = SpawnRules::default();
+        let target = WinTarget(8);
+        let turn = snapshot(&[(0, 0, 4), (1, 0, 4)])
+            .play(Direction::Left, &rules)
+            .unwrap();
+        assert_eq!(turn.state(&target, false), RunState::Won);
+        assert_eq!(turn.state(&target, true), RunState::Playing);
+
+        // already on the board, but not merged this turn
+        let turn = snapshot(&[(1, 0, 8)])
+            .play(Direction::Left, &rules)
+            .unwrap();
+        assert_eq!(turn.state(&target, false), RunState::Playing);
+    }
+}
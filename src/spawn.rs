@@ -0,0 +1,57 @@
+use crate::components::Position;
+use crate::grid::BoardModel;
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Which tiles appear, and how many of them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SpawnRules {
+    /// tile value and its relative weight
+    pub weights: Vec<(u32, u32)>,
+    pub tiles_per_turn: usize,
+    pub starting_tiles: usize,
+}
+
+impl Default for SpawnRules {
+    // the original game: a 4 one time in ten,
+    // otherwise a 2
+    fn default() -> Self {
+        SpawnRules {
+            weights: vec![(2, 9), (4, 1)],
+            tiles_per_turn: 1,
+            starting_tiles: 2,
+        }
+    }
+}
+
+impl SpawnRules {
+    pub fn pick_value<R: Rng + ?Sized>(&self, rng: &mut R) -> u32 {
+        self.weights
+            .choose_weighted(rng, |(_, weight)| *weight)
+            .map(|(value, _)| *value)
+            .expect("spawn weights to contain at least one non-zero weight")
+    }
+
+    /// Place up to `count` new tiles on empty cells of the
+    /// grid, returning the tiles that were placed. There
+    /// may be fewer if the grid fills up.
+    pub fn spawn<B: BoardModel, R: Rng + ?Sized>(
+        &self,
+        grid: &mut B,
+        count: usize,
+        rng: &mut R,
+    ) -> Vec<(Position, u32)> {
+        let positions = grid
+            .empty_positions()
+            .into_iter()
+            .choose_multiple(rng, count);
+        positions
+            .into_iter()
+            .map(|pos| {
+                let value = self.pick_value(rng);
+                grid.set(pos, Some(value));
+                (pos, value)
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,111 @@
+use ron::ser::PrettyConfig;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug)]
+pub enum StorageError {
+    Io(io::Error),
+    Format(ron::Error),
+    // the file was written by a version of the game
+    // that we don't know how to read
+    UnsupportedVersion(u32),
+    InvalidName(String),
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StorageError::Io(err) => write!(f, "{}", err),
+            StorageError::Format(err) => write!(f, "{}", err),
+            StorageError::UnsupportedVersion(version) => {
+                write!(f, "unsupported file version {}", version)
+            }
+            StorageError::InvalidName(name) => write!(f, "{:?} is not a valid name", name),
+        }
+    }
+}
+
+impl std::error::Error for StorageError {}
+
+impl From<io::Error> for StorageError {
+    fn from(err: io::Error) -> Self {
+        StorageError::Io(err)
+    }
+}
+
+impl From<ron::Error> for StorageError {
+    fn from(err: ron::Error) -> Self {
+        StorageError::Format(err)
+    }
+}
+
+/// Where the game keeps its files, following each
+/// platform's convention for per-user application data.
+pub fn data_dir() -> PathBuf {
+    let base = if cfg!(target_os = "windows") {
+        std::env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        std::env::var_os("HOME").map(|home| {
+            PathBuf::from(home)
+                .join("Library")
+                .join("Application Support")
+        })
+    } else {
+        std::env::var_os("XDG_DATA_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| {
+                std::env::var_os("HOME")
+                    .map(|home| PathBuf::from(home).join(".local").join("share"))
+            })
+    };
+    // with nowhere better to go, use the working directory
+    base.unwrap_or_else(|| PathBuf::from(".")).join("2048")
+}
+
+pub fn load<T: DeserializeOwned>(path: &Path) -> Result<T, StorageError> {
+    let contents = fs::read_to_string(path)?;
+    Ok(ron::from_str(&contents)?)
+}
+
+// read on its own first, so a file from another version
+// fails with a useful error instead of a parse error
+#[derive(Deserialize)]
+struct Version {
+    version: u32,
+}
+
+/// `load` for a file with a `version` field, which has to
+/// be `version`.
+pub fn load_versioned<T: DeserializeOwned>(path: &Path, version: u32) -> Result<T, StorageError> {
+    let contents = fs::read_to_string(path)?;
+    let Version { version: found } = ron::from_str(&contents)?;
+    if found != version {
+        return Err(StorageError::UnsupportedVersion(found));
+    }
+    Ok(ron::from_str(&contents)?)
+}
+
+/// Write to a temporary file next to `path` and move it
+/// into place, so a crash never leaves half a file behind.
+pub fn save<T: Serialize>(path: &Path, value: &T) -> Result<(), StorageError> {
+    save_with(path, value, PrettyConfig::default())
+}
+
+pub fn save_with<T: Serialize>(
+    path: &Path,
+    value: &T,
+    config: PrettyConfig,
+) -> Result<(), StorageError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let contents = ron::ser::to_string_pretty(value, config)?;
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, contents)?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
@@ -1,11 +1,25 @@
-use crate::components::Game;
+use crate::playback::{Playback, PlaybackEvent};
+use crate::{palette, HistoryEvent};
 use bevy::prelude::*;
+use boxes::components::{Game, RunState, WinTarget};
+use boxes::history::History;
+use boxes::options::{Options, Theme};
+use boxes::rng::GameRng;
+use boxes::slots::SaveSlots;
 
 mod buttons;
+mod hint;
+mod slots;
 use buttons::*;
+use hint::*;
+use slots::*;
 
 pub struct ScoreDisplay;
 pub struct BestScoreDisplay;
+pub struct SeedDisplay;
+pub struct WinOverlay;
+pub struct PlaybackBar;
+pub struct PlaybackDisplay;
 
 pub struct GameUiPlugin;
 
@@ -13,8 +27,48 @@ impl Plugin for GameUiPlugin {
     fn build(&self, app: &mut AppBuilder) {
         app.add_startup_system(setup_ui.system())
             .init_resource::<ButtonMaterials>()
+            .init_resource::<SaveSlots>()
+            .init_resource::<HintSolver>()
+            .init_resource::<Hint>()
+            .add_event::<RefreshSlotsEvent>()
             .add_system(button_system.system())
-            .add_system(scoreboard.system());
+            .add_system(game_button.system())
+            .add_system(keep_playing_button.system())
+            .add_system(history_button.system())
+            .add_system(continue_button.system())
+            .add_system(slots_button.system())
+            .add_system(playback_button.system())
+            .add_system(auto_button.system())
+            .add_system(hint_button.system())
+            .add_system(clear_hint.system())
+            .add_system(scoreboard.system())
+            .add_system_set(
+                SystemSet::on_enter(RunState::Won).with_system(spawn_win_overlay.system()),
+            )
+            .add_system_set(
+                SystemSet::on_exit(RunState::Won).with_system(despawn_win_overlay.system()),
+            )
+            .add_system_set(
+                SystemSet::on_enter(RunState::Replay).with_system(spawn_playback_bar.system()),
+            )
+            .add_system_set(
+                SystemSet::on_update(RunState::Replay).with_system(playback_status.system()),
+            )
+            .add_system_set(
+                SystemSet::on_exit(RunState::Replay).with_system(despawn_playback_bar.system()),
+            )
+            .add_system_set(
+                SystemSet::on_enter(RunState::Slots).with_system(open_slot_panel.system()),
+            )
+            .add_system_set(
+                SystemSet::on_update(RunState::Slots)
+                    .with_system(slot_name_input.system())
+                    .with_system(slot_buttons.system())
+                    .with_system(refresh_slot_panel.system()),
+            )
+            .add_system_set(
+                SystemSet::on_exit(RunState::Slots).with_system(close_slot_panel.system()),
+            );
     }
 }
 
@@ -23,7 +77,10 @@ fn setup_ui(
     mut materials: ResMut<Assets<ColorMaterial>>,
     asset_server: Res<AssetServer>,
     button_materials: Res<ButtonMaterials>,
+    history: Res<History>,
+    options: Res<Options>,
 ) {
+    let text_color = palette(options.theme.unwrap_or(Theme::Dark)).text;
     commands
         .spawn_bundle(NodeBundle {
             style: Style {
@@ -44,7 +101,7 @@ fn setup_ui(
                     TextStyle {
                         font: asset_server.load("fonts/FiraSans-Bold.ttf"),
                         font_size: 40.0,
-                        color: Color::WHITE,
+                        color: text_color,
                         ..Default::default()
                     },
                     TextAlignment {
@@ -174,6 +231,62 @@ fn setup_ui(
                                 .insert(BestScoreDisplay);
                         });
                     // end best scorebox
+                    // seedbox
+                    parent
+                        .spawn_bundle(NodeBundle {
+                            style: Style {
+                                display: Display::Flex,
+                                flex_direction: FlexDirection::ColumnReverse,
+                                align_items: AlignItems::Center,
+                                margin: Rect {
+                                    left: Val::Px(20.0),
+                                    right: Val::Px(0.0),
+                                    top: Val::Px(0.0),
+                                    bottom: Val::Px(0.0),
+                                },
+                                border: Rect::all(Val::Px(10.0)),
+                                ..Default::default()
+                            },
+                            material: materials.add(Color::rgb(0.75, 0.75, 0.9).into()),
+                            ..Default::default()
+                        })
+                        .with_children(|parent| {
+                            parent.spawn_bundle(TextBundle {
+                                text: Text::with_section(
+                                    "Seed",
+                                    TextStyle {
+                                        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                                        font_size: 15.0,
+                                        color: Color::WHITE,
+                                        ..Default::default()
+                                    },
+                                    TextAlignment {
+                                        vertical: VerticalAlign::Center,
+                                        horizontal: HorizontalAlign::Center,
+                                    },
+                                ),
+                                ..Default::default()
+                            });
+                            parent
+                                .spawn_bundle(TextBundle {
+                                    text: Text::with_section(
+                                        "<seed>",
+                                        TextStyle {
+                                            font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                                            font_size: 20.0,
+                                            color: Color::WHITE,
+                                            ..Default::default()
+                                        },
+                                        TextAlignment {
+                                            vertical: VerticalAlign::Center,
+                                            horizontal: HorizontalAlign::Center,
+                                        },
+                                    ),
+                                    ..Default::default()
+                                })
+                                .insert(SeedDisplay);
+                        });
+                    // end seedbox
                 });
             parent
                 .spawn_bundle(ButtonBundle {
@@ -197,6 +310,7 @@ fn setup_ui(
                     material: button_materials.normal.clone(),
                     ..Default::default()
                 })
+                .insert(GameButton)
                 .with_children(|parent| {
                     parent.spawn_bundle(TextBundle {
                         text: Text::with_section(
@@ -211,15 +325,226 @@ fn setup_ui(
                         ..Default::default()
                     });
                 });
+            spawn_button(
+                parent,
+                &button_materials,
+                &asset_server,
+                "Continue",
+                ContinueButton,
+            );
+            spawn_button(
+                parent,
+                &button_materials,
+                &asset_server,
+                "Saves",
+                SlotsButton,
+            );
+            spawn_button(parent, &button_materials, &asset_server, "Auto", AutoButton);
+            spawn_button(parent, &button_materials, &asset_server, "Hint", HintButton);
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        "",
+                        TextStyle {
+                            font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                            font_size: 15.0,
+                            color: text_color,
+                        },
+                        Default::default(),
+                    ),
+                    ..Default::default()
+                })
+                .insert(HintText);
+            // competitive modes turn undo off entirely
+            if history.limit() != Some(0) {
+                spawn_button(
+                    parent,
+                    &button_materials,
+                    &asset_server,
+                    "Undo",
+                    HistoryButton(HistoryEvent::Undo),
+                );
+                spawn_button(
+                    parent,
+                    &button_materials,
+                    &asset_server,
+                    "Redo",
+                    HistoryButton(HistoryEvent::Redo),
+                );
+            }
         });
 }
 
+fn spawn_win_overlay(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    asset_server: Res<AssetServer>,
+    button_materials: Res<ButtonMaterials>,
+    win_target: Res<WinTarget>,
+) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                display: Display::Flex,
+                position_type: PositionType::Absolute,
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                flex_direction: FlexDirection::ColumnReverse,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            material: materials.add(Color::rgba(0.04, 0.04, 0.1, 0.8).into()),
+            ..Default::default()
+        })
+        .insert(WinOverlay)
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                text: Text::with_section(
+                    format!("You reached {}!", win_target.0),
+                    TextStyle {
+                        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                        font_size: 40.0,
+                        color: Color::WHITE,
+                    },
+                    TextAlignment {
+                        vertical: VerticalAlign::Center,
+                        horizontal: HorizontalAlign::Center,
+                    },
+                ),
+                ..Default::default()
+            });
+            parent
+                .spawn_bundle(ButtonBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(130.0), Val::Px(30.0)),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        margin: Rect::all(Val::Px(20.0)),
+                        ..Default::default()
+                    },
+                    material: button_materials.normal.clone(),
+                    ..Default::default()
+                })
+                .insert(KeepPlayingButton)
+                .with_children(|parent| {
+                    parent.spawn_bundle(TextBundle {
+                        text: Text::with_section(
+                            "Keep going",
+                            TextStyle {
+                                font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                                font_size: 20.0,
+                                color: Color::rgb(0.9, 0.9, 0.9),
+                            },
+                            Default::default(),
+                        ),
+                        ..Default::default()
+                    });
+                });
+        });
+}
+
+fn despawn_win_overlay(mut commands: Commands, overlays: Query<Entity, With<WinOverlay>>) {
+    for entity in overlays.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+// replay controls along the top of the window
+fn spawn_playback_bar(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    asset_server: Res<AssetServer>,
+    button_materials: Res<ButtonMaterials>,
+    options: Res<Options>,
+) {
+    let text_color = palette(options.theme.unwrap_or(Theme::Dark)).text;
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                display: Display::Flex,
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(0.0),
+                    ..Default::default()
+                },
+                size: Size::new(Val::Percent(100.0), Val::Auto),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            material: materials.add(Color::NONE.into()),
+            ..Default::default()
+        })
+        .insert(PlaybackBar)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle {
+                    style: Style {
+                        min_size: Size::new(Val::Px(200.0), Val::Auto),
+                        ..Default::default()
+                    },
+                    text: Text::with_section(
+                        "<playback>",
+                        TextStyle {
+                            font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                            font_size: 20.0,
+                            color: text_color,
+                        },
+                        Default::default(),
+                    ),
+                    ..Default::default()
+                })
+                .insert(PlaybackDisplay);
+            for (label, event) in [
+                ("Play", PlaybackEvent::PlayPause),
+                ("Back", PlaybackEvent::StepBack),
+                ("Step", PlaybackEvent::StepForward),
+                ("Slower", PlaybackEvent::Slower),
+                ("Faster", PlaybackEvent::Faster),
+            ]
+            .iter()
+            {
+                spawn_button(
+                    parent,
+                    &button_materials,
+                    &asset_server,
+                    label,
+                    PlaybackButton(*event),
+                );
+            }
+        });
+}
+
+fn despawn_playback_bar(mut commands: Commands, bars: Query<Entity, With<PlaybackBar>>) {
+    for entity in bars.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+// e.g. `Move 12/340 at 2x, paused`
+fn playback_status(playback: Res<Playback>, mut texts: Query<&mut Text, With<PlaybackDisplay>>) {
+    if !playback.is_changed() {
+        return;
+    }
+    for mut text in texts.iter_mut() {
+        text.sections[0].value = format!(
+            "Move {}/{} at {}x{}",
+            playback.position(),
+            playback.total_moves(),
+            playback.speed,
+            if playback.playing { "" } else { ", paused" }
+        );
+    }
+}
+
 // update the score displayed during the game
 fn scoreboard(
     game: Res<Game>,
+    game_rng: Res<GameRng>,
     mut query_scores: QuerySet<(
         Query<&mut Text, With<ScoreDisplay>>,
         Query<&mut Text, With<BestScoreDisplay>>,
+        Query<&mut Text, With<SeedDisplay>>,
     )>,
 ) {
     let mut text = query_scores.q0_mut().single_mut().unwrap();
@@ -227,4 +552,7 @@ fn scoreboard(
 
     let mut best_text = query_scores.q1_mut().single_mut().unwrap();
     best_text.sections[0].value = game.score_best.to_string();
+
+    let mut seed_text = query_scores.q2_mut().single_mut().unwrap();
+    seed_text.sections[0].value = game_rng.seed().to_string();
 }
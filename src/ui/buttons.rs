@@ -1,5 +1,12 @@
-use crate::components::RunState;
+use crate::autoplay::Autoplay;
+use crate::playback::PlaybackEvent;
+use crate::{HistoryEvent, LoadGameEvent};
+use bevy::ecs::component::Component;
 use bevy::prelude::*;
+use boxes::components::{Block, Board, Game, Position, RunState};
+use boxes::grid::Grid;
+use boxes::rng::GameRng;
+use boxes::save::SavedGame;
 
 pub struct ButtonMaterials {
     pub normal: Handle<ColorMaterial>,
@@ -18,16 +25,95 @@ impl FromWorld for ButtonMaterials {
     }
 }
 
+// the End Game/New Game button under the scores
+pub struct GameButton;
+// shown on the victory overlay
+pub struct KeepPlayingButton;
+pub struct HistoryButton(pub HistoryEvent);
+// picks the autosaved game back up
+pub struct ContinueButton;
+// opens the save slot panel
+pub struct SlotsButton;
+pub struct PlaybackButton(pub PlaybackEvent);
+// lets the computer play
+pub struct AutoButton;
+// asks the computer for a move
+pub struct HintButton;
+
+// a small labelled button, `marker` decides which
+// system reacts to it
+pub fn spawn_button(
+    parent: &mut ChildBuilder,
+    button_materials: &ButtonMaterials,
+    asset_server: &AssetServer,
+    label: &str,
+    marker: impl Component,
+) {
+    parent
+        .spawn_bundle(ButtonBundle {
+            style: Style {
+                size: Size::new(Val::Px(90.0), Val::Px(30.0)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                margin: Rect {
+                    left: Val::Px(5.0),
+                    right: Val::Px(5.0),
+                    top: Val::Px(20.0),
+                    bottom: Val::Px(20.0),
+                },
+                ..Default::default()
+            },
+            material: button_materials.normal.clone(),
+            ..Default::default()
+        })
+        .insert(marker)
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                text: Text::with_section(
+                    label,
+                    TextStyle {
+                        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                        font_size: 20.0,
+                        color: Color::rgb(0.9, 0.9, 0.9),
+                    },
+                    Default::default(),
+                ),
+                ..Default::default()
+            });
+        });
+}
+
+// every button gets the same hover and press colors,
+// what a click does is up to the button's own system
 pub fn button_system(
     button_materials: Res<ButtonMaterials>,
     mut interaction_query: Query<
-        (&Interaction, &mut Handle<ColorMaterial>, &Children),
+        (&Interaction, &mut Handle<ColorMaterial>),
         (Changed<Interaction>, With<Button>),
     >,
+) {
+    for (interaction, mut material) in interaction_query.iter_mut() {
+        match *interaction {
+            Interaction::Clicked => {
+                *material = button_materials.pressed.clone();
+            }
+            Interaction::Hovered => {
+                *material = button_materials.hovered.clone();
+            }
+            Interaction::None => {
+                *material = button_materials.normal.clone();
+            }
+        }
+    }
+}
+
+pub fn game_button(
+    interaction_query: Query<(&Interaction, &Children), (Changed<Interaction>, With<GameButton>)>,
     mut text_query: Query<&mut Text>,
     mut run_state: ResMut<State<RunState>>,
+    mut game_rng: ResMut<GameRng>,
 ) {
-    for (interaction, mut material, children) in interaction_query.iter_mut() {
+    for (interaction, children) in interaction_query.iter() {
         let mut text = text_query
             .get_mut(
                 *children
@@ -36,33 +122,124 @@ pub fn button_system(
             )
             .unwrap();
         match *interaction {
-            Interaction::Clicked => {
-                *material = button_materials.pressed.clone();
-
-                match run_state.current() {
-                    RunState::Playing => {
-                        run_state.set(RunState::GameOver).unwrap();
-                    }
-                    RunState::GameOver => {
-                        run_state.set(RunState::Playing).unwrap();
-                    }
+            Interaction::Clicked => match run_state.current() {
+                RunState::Playing => {
+                    run_state.set(RunState::GameOver).unwrap();
                 }
-            }
-            Interaction::Hovered => {
-                *material = button_materials.hovered.clone();
-            }
-            Interaction::None => {
-                match run_state.current() {
-                    RunState::Playing => {
-                        text.sections[0].value = "End Game".to_string();
-                    }
-                    RunState::GameOver => {
-                        text.sections[0].value = "New Game".to_string();
-                    }
+                RunState::GameOver | RunState::Replay => {
+                    // every new game gets its own seed
+                    game_rng.reseed(rand::random());
+                    run_state.set(RunState::Playing).unwrap();
+                }
+                RunState::Won => {
+                    game_rng.reseed(rand::random());
+                    // Won sits on top of the Playing game, so
+                    // replace the whole stack to start over
+                    run_state.replace(RunState::Playing).unwrap();
+                }
+                // the slot panel is in the way
+                RunState::Slots => {}
+            },
+            Interaction::Hovered => {}
+            Interaction::None => match run_state.current() {
+                RunState::Playing => {
+                    text.sections[0].value = "End Game".to_string();
                 }
+                RunState::GameOver | RunState::Won | RunState::Replay => {
+                    text.sections[0].value = "New Game".to_string();
+                }
+                RunState::Slots => {}
+            },
+        }
+    }
+}
 
-                *material = button_materials.normal.clone();
+pub fn keep_playing_button(
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<KeepPlayingButton>)>,
+    mut run_state: ResMut<State<RunState>>,
+    mut game: ResMut<Game>,
+    query_board: Query<&Board>,
+    blocks: Query<(&Position, &Block)>,
+) {
+    for interaction in interaction_query.iter() {
+        if *interaction == Interaction::Clicked {
+            let board = query_board.single().expect("expect there to be a board");
+            let grid = Grid::from_tiles(
+                board.width,
+                board.height,
+                blocks.iter().map(|(pos, block)| (*pos, block.value)),
+            );
+            if grid.has_legal_move() {
+                game.endless = true;
+                run_state.pop().unwrap();
+            } else {
+                // the winning move also filled the board
+                run_state.replace(RunState::GameOver).unwrap();
             }
         }
     }
 }
+
+pub fn history_button(
+    interaction_query: Query<(&Interaction, &HistoryButton), Changed<Interaction>>,
+    mut history_writer: EventWriter<HistoryEvent>,
+) {
+    for (interaction, HistoryButton(event)) in interaction_query.iter() {
+        if *interaction == Interaction::Clicked {
+            history_writer.send(*event);
+        }
+    }
+}
+
+pub fn playback_button(
+    interaction_query: Query<(&Interaction, &PlaybackButton), Changed<Interaction>>,
+    mut playback_writer: EventWriter<PlaybackEvent>,
+) {
+    for (interaction, PlaybackButton(event)) in interaction_query.iter() {
+        if *interaction == Interaction::Clicked {
+            playback_writer.send(*event);
+        }
+    }
+}
+
+pub fn continue_button(
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<ContinueButton>)>,
+    mut load_writer: EventWriter<LoadGameEvent>,
+) {
+    for interaction in interaction_query.iter() {
+        if *interaction == Interaction::Clicked {
+            load_writer.send(LoadGameEvent(SavedGame::autosave_path()));
+        }
+    }
+}
+
+pub fn auto_button(
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<AutoButton>)>,
+    buttons: Query<&Children, With<AutoButton>>,
+    mut text_query: Query<&mut Text>,
+    mut autoplay: ResMut<Autoplay>,
+) {
+    for interaction in interaction_query.iter() {
+        if *interaction == Interaction::Clicked {
+            autoplay.toggle();
+        }
+    }
+    // the hotkey, a takeover or the game ending can all
+    // change it too
+    if !autoplay.is_changed() {
+        return;
+    }
+    for children in buttons.iter() {
+        let mut text = text_query
+            .get_mut(
+                *children
+                    .first()
+                    .expect("expect button to have a first child"),
+            )
+            .unwrap();
+        text.sections[0].value = match autoplay.enabled {
+            true => "Stop".to_string(),
+            false => "Auto".to_string(),
+        };
+    }
+}
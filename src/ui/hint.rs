@@ -0,0 +1,161 @@
+use super::buttons::HintButton;
+use bevy::prelude::*;
+use boxes::ai::{best, Expectimax};
+use boxes::components::{Block, Board, BoardConfig, Position, RunState};
+use boxes::grid::{Direction, Grid};
+use boxes::options::MAX_AI_DEPTH;
+use boxes::spawn::SpawnRules;
+use std::time::Duration;
+
+// how long a hint may think for, it blocks the frame
+const HINT_TIME: Duration = Duration::from_millis(150);
+
+pub struct HintSolver(Expectimax);
+
+impl FromWorld for HintSolver {
+    fn from_world(world: &mut World) -> Self {
+        let rules = world
+            .get_resource::<SpawnRules>()
+            .expect("expect spawn rules to be inserted before the UI")
+            .clone();
+        // as deep as it gets in `HINT_TIME`
+        let solver = Expectimax::new(MAX_AI_DEPTH, rules);
+        // so the first hint doesn't spend its time on them
+        let config = world
+            .get_resource::<BoardConfig>()
+            .expect("expect the board config to be inserted before the UI");
+        solver.prepare(config.width, config.height);
+        HintSolver(solver)
+    }
+}
+
+/// The board the shown hint is for, it's taken down as
+/// soon as the board changes.
+#[derive(Default)]
+pub struct Hint {
+    grid: Option<Grid>,
+}
+
+// the arrow drawn over the board
+pub struct HintArrow;
+// the value of every move, in the panel
+pub struct HintText;
+
+fn arrow(direction: Direction) -> &'static str {
+    match direction {
+        Direction::Left => "←",
+        Direction::Right => "→",
+        Direction::Up => "↑",
+        Direction::Down => "↓",
+    }
+}
+
+fn current_grid(
+    query_board: &Query<(&Board, &Transform)>,
+    blocks: &Query<(&Position, &Block)>,
+) -> Grid {
+    let (board, _) = query_board.single().expect("expect there to be a board");
+    Grid::from_tiles(
+        board.width,
+        board.height,
+        blocks.iter().map(|(pos, block)| (*pos, block.value)),
+    )
+}
+
+pub fn hint_button(
+    mut commands: Commands,
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<HintButton>)>,
+    run_state: Res<State<RunState>>,
+    asset_server: Res<AssetServer>,
+    solver: Res<HintSolver>,
+    mut hint: ResMut<Hint>,
+    query_board: Query<(&Board, &Transform)>,
+    blocks: Query<(&Position, &Block)>,
+    arrows: Query<Entity, With<HintArrow>>,
+    mut texts: Query<&mut Text, With<HintText>>,
+) {
+    let clicked = interaction_query
+        .iter()
+        .any(|interaction| *interaction == Interaction::Clicked);
+    if !clicked || *run_state.current() != RunState::Playing {
+        return;
+    }
+
+    let grid = current_grid(&query_board, &blocks);
+    // already done unless a loaded game changed the size
+    solver.0.prepare(grid.width(), grid.height());
+    let (values, depth) = solver.0.grid_move_values_within(&grid, HINT_TIME);
+    for entity in arrows.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    if let Some((direction, _)) = best(&values) {
+        let (_, board_transform) = query_board.single().expect("expect there to be a board");
+        let mut transform = *board_transform;
+        // over the blocks and their text
+        transform.translation.z = 10.0;
+        commands
+            .spawn_bundle(Text2dBundle {
+                text: Text::with_section(
+                    arrow(direction),
+                    TextStyle {
+                        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                        font_size: 160.0,
+                        color: Color::rgba(0.9, 0.25, 0.2, 0.8),
+                    },
+                    TextAlignment {
+                        vertical: VerticalAlign::Center,
+                        horizontal: HorizontalAlign::Center,
+                    },
+                ),
+                transform,
+                ..Default::default()
+            })
+            .insert(HintArrow);
+    }
+
+    // e.g. `← 1607245`, with `-` for a move that does nothing
+    let lines: Vec<String> = Direction::ALL
+        .iter()
+        .map(|&direction| {
+            let value = values
+                .iter()
+                .find(|(moved, _)| *moved == direction)
+                .map_or("-".to_string(), |(_, value)| format!("{:.0}", value));
+            format!("{} {}", arrow(direction), value)
+        })
+        .collect();
+    let searched = match depth {
+        0 => "no time to look ahead".to_string(),
+        1 => "1 move ahead".to_string(),
+        depth => format!("{} moves ahead", depth),
+    };
+    for mut text in texts.iter_mut() {
+        text.sections[0].value = format!("{}\n{}", lines.join("\n"), searched);
+    }
+    hint.grid = Some(grid);
+}
+
+// a hint is only good for the board it was worked out on
+pub fn clear_hint(
+    mut commands: Commands,
+    mut hint: ResMut<Hint>,
+    query_board: Query<(&Board, &Transform)>,
+    blocks: Query<(&Position, &Block)>,
+    arrows: Query<Entity, With<HintArrow>>,
+    mut texts: Query<&mut Text, With<HintText>>,
+) {
+    let hinted = match &hint.grid {
+        Some(grid) => grid,
+        None => return,
+    };
+    if query_board.single().is_ok() && *hinted == current_grid(&query_board, &blocks) {
+        return;
+    }
+    for entity in arrows.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    for mut text in texts.iter_mut() {
+        text.sections[0].value = String::new();
+    }
+    hint.grid = None;
+}
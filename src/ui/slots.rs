@@ -0,0 +1,315 @@
+use super::buttons::{spawn_button, ButtonMaterials, SlotsButton};
+use crate::{current_snapshot, LoadGameEvent, Recording};
+use bevy::prelude::*;
+use boxes::components::{Block, Board, Game, Position, RunState};
+use boxes::rng::GameRng;
+use boxes::save::SavedGame;
+use boxes::slots::{SaveSlots, SlotInfo, MAX_SLOT_NAME_LENGTH};
+use std::time::SystemTime;
+
+// set when the panel opens, `game_state` is whatever the
+// panel was pushed on top of
+pub struct SlotMenu {
+    game_state: RunState,
+    name: String,
+}
+
+pub struct SlotPanel;
+pub struct SlotNameText;
+pub enum SlotButton {
+    Save,
+    Load(String),
+    Delete(String),
+    Close,
+}
+
+// the slot list changed on disk
+pub struct RefreshSlotsEvent;
+
+pub fn slots_button(
+    mut commands: Commands,
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<SlotsButton>)>,
+    mut run_state: ResMut<State<RunState>>,
+) {
+    for interaction in interaction_query.iter() {
+        // a replay's board isn't a game that could be saved
+        if *interaction == Interaction::Clicked
+            && !matches!(run_state.current(), RunState::Slots | RunState::Replay)
+        {
+            commands.insert_resource(SlotMenu {
+                game_state: run_state.current().clone(),
+                name: String::new(),
+            });
+            run_state.push(RunState::Slots).unwrap();
+        }
+    }
+}
+
+pub fn open_slot_panel(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    asset_server: Res<AssetServer>,
+    button_materials: Res<ButtonMaterials>,
+    slots: Res<SaveSlots>,
+) {
+    spawn_slot_panel(
+        &mut commands,
+        &mut materials,
+        &asset_server,
+        &button_materials,
+        &slots,
+    );
+}
+
+// rebuilding the whole panel is simpler than patching
+// up the list
+pub fn refresh_slot_panel(
+    mut commands: Commands,
+    mut refresh_reader: EventReader<RefreshSlotsEvent>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    asset_server: Res<AssetServer>,
+    button_materials: Res<ButtonMaterials>,
+    slots: Res<SaveSlots>,
+    panels: Query<Entity, With<SlotPanel>>,
+) {
+    if refresh_reader.iter().next().is_none() {
+        return;
+    }
+    for entity in panels.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    spawn_slot_panel(
+        &mut commands,
+        &mut materials,
+        &asset_server,
+        &button_materials,
+        &slots,
+    );
+}
+
+pub fn close_slot_panel(mut commands: Commands, panels: Query<Entity, With<SlotPanel>>) {
+    for entity in panels.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    commands.remove_resource::<SlotMenu>();
+}
+
+fn spawn_slot_panel(
+    commands: &mut Commands,
+    materials: &mut Assets<ColorMaterial>,
+    asset_server: &AssetServer,
+    button_materials: &ButtonMaterials,
+    slots: &SaveSlots,
+) {
+    let slot_list = slots.list().unwrap_or_else(|err| {
+        warn!("couldn't list save slots: {}", err);
+        vec![]
+    });
+    let text_style = |font_size: f32| TextStyle {
+        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+        font_size,
+        color: Color::WHITE,
+    };
+    let row_style = Style {
+        display: Display::Flex,
+        align_items: AlignItems::Center,
+        justify_content: JustifyContent::Center,
+        ..Default::default()
+    };
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                display: Display::Flex,
+                position_type: PositionType::Absolute,
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                flex_direction: FlexDirection::ColumnReverse,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            material: materials.add(Color::rgba(0.04, 0.04, 0.1, 0.9).into()),
+            ..Default::default()
+        })
+        .insert(SlotPanel)
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                text: Text::with_section("Saved games", text_style(40.0), Default::default()),
+                ..Default::default()
+            });
+            // name entry
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: row_style.clone(),
+                    material: materials.add(Color::NONE.into()),
+                    ..Default::default()
+                })
+                .with_children(|parent| {
+                    parent
+                        .spawn_bundle(TextBundle {
+                            style: Style {
+                                min_size: Size::new(Val::Px(240.0), Val::Auto),
+                                ..Default::default()
+                            },
+                            text: Text::with_section(
+                                "type a name",
+                                text_style(20.0),
+                                Default::default(),
+                            ),
+                            ..Default::default()
+                        })
+                        .insert(SlotNameText);
+                    spawn_button(
+                        parent,
+                        button_materials,
+                        asset_server,
+                        "Save",
+                        SlotButton::Save,
+                    );
+                });
+            for slot in slot_list {
+                parent
+                    .spawn_bundle(NodeBundle {
+                        style: row_style.clone(),
+                        material: materials.add(Color::NONE.into()),
+                        ..Default::default()
+                    })
+                    .with_children(|parent| {
+                        parent.spawn_bundle(TextBundle {
+                            style: Style {
+                                min_size: Size::new(Val::Px(240.0), Val::Auto),
+                                ..Default::default()
+                            },
+                            text: Text::with_section(
+                                describe_slot(&slot),
+                                text_style(20.0),
+                                Default::default(),
+                            ),
+                            ..Default::default()
+                        });
+                        spawn_button(
+                            parent,
+                            button_materials,
+                            asset_server,
+                            "Load",
+                            SlotButton::Load(slot.name.clone()),
+                        );
+                        spawn_button(
+                            parent,
+                            button_materials,
+                            asset_server,
+                            "Delete",
+                            SlotButton::Delete(slot.name),
+                        );
+                    });
+            }
+            spawn_button(
+                parent,
+                button_materials,
+                asset_server,
+                "Close",
+                SlotButton::Close,
+            );
+        });
+}
+
+// e.g. `evening: 1234 (max 256), 5m ago`
+fn describe_slot(slot: &SlotInfo) -> String {
+    let age = SystemTime::now()
+        .duration_since(slot.saved_at)
+        .map(|age| age.as_secs())
+        .unwrap_or(0);
+    let ago = match age {
+        0..=59 => "just now".to_string(),
+        60..=3599 => format!("{}m ago", age / 60),
+        3600..=86399 => format!("{}h ago", age / 3600),
+        _ => format!("{}d ago", age / 86400),
+    };
+    format!(
+        "{}: {} (max {}), {}",
+        slot.name, slot.score, slot.max_tile, ago
+    )
+}
+
+pub fn slot_name_input(
+    mut char_reader: EventReader<ReceivedCharacter>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut menu: ResMut<SlotMenu>,
+    mut texts: Query<&mut Text, With<SlotNameText>>,
+) {
+    let mut changed = false;
+    for event in char_reader.iter() {
+        let c = event.char;
+        if (c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == ' ')
+            && menu.name.len() < MAX_SLOT_NAME_LENGTH
+        {
+            menu.name.push(c);
+            changed = true;
+        }
+    }
+    if keyboard_input.just_pressed(KeyCode::Back) {
+        changed |= menu.name.pop().is_some();
+    }
+    if !changed {
+        return;
+    }
+    for mut text in texts.iter_mut() {
+        text.sections[0].value = if menu.name.is_empty() {
+            "type a name".to_string()
+        } else {
+            menu.name.clone()
+        };
+    }
+}
+
+pub fn slot_buttons(
+    interaction_query: Query<(&Interaction, &SlotButton), Changed<Interaction>>,
+    mut menu: ResMut<SlotMenu>,
+    slots: Res<SaveSlots>,
+    mut run_state: ResMut<State<RunState>>,
+    mut load_writer: EventWriter<LoadGameEvent>,
+    mut refresh_writer: EventWriter<RefreshSlotsEvent>,
+    query_board: Query<&Board>,
+    blocks: Query<(&Position, &Block)>,
+    game: Res<Game>,
+    game_rng: Res<GameRng>,
+    recording: Res<Recording>,
+) {
+    for (interaction, button) in interaction_query.iter() {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+        match button {
+            SlotButton::Save => {
+                let board = query_board.single().expect("expect there to be a board");
+                let mut saved = SavedGame::new(
+                    current_snapshot(
+                        board,
+                        blocks.iter().map(|(pos, block)| (*pos, block.value)),
+                        &game,
+                        &game_rng,
+                    ),
+                    game.endless,
+                    menu.game_state.clone(),
+                );
+                saved.replay = recording.in_progress(game.moves);
+                match slots.save(&menu.name, &saved) {
+                    Ok(()) => {
+                        // the text goes with the rebuilt panel
+                        menu.name.clear();
+                        refresh_writer.send(RefreshSlotsEvent);
+                    }
+                    Err(err) => warn!("couldn't save slot: {}", err),
+                }
+            }
+            SlotButton::Load(name) => match slots.path(name) {
+                Ok(path) => load_writer.send(LoadGameEvent(path)),
+                Err(err) => warn!("couldn't load slot: {}", err),
+            },
+            SlotButton::Delete(name) => match slots.delete(name) {
+                Ok(()) => refresh_writer.send(RefreshSlotsEvent),
+                Err(err) => warn!("couldn't delete slot {}: {}", name, err),
+            },
+            SlotButton::Close => run_state.pop().unwrap(),
+        }
+    }
+}
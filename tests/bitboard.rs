@@ -0,0 +1,150 @@
+//! Checks `BitBoard` against `Grid`, the rules the game is
+//! actually played with, on random boards of every size.
+
+use boxes::ai::Expectimax;
+use boxes::bitboard::BitBoard;
+use boxes::components::{Position, MAX_BOARD_SIZE, MIN_BOARD_SIZE};
+use boxes::grid::{Direction, Grid};
+use boxes::rng::GameRng;
+use boxes::spawn::SpawnRules;
+use rand::Rng;
+use std::convert::TryFrom;
+
+fn sizes() -> impl Iterator<Item = (u8, u8)> {
+    (MIN_BOARD_SIZE..=MAX_BOARD_SIZE)
+        .flat_map(|width| (MIN_BOARD_SIZE..=MAX_BOARD_SIZE).map(move |height| (width, height)))
+}
+
+// a board with about a third of its cells empty, and
+// tiles small enough to often sit next to an equal one
+fn random_grid(width: u8, height: u8, rng: &mut GameRng) -> Grid {
+    let mut grid = Grid::new(width, height);
+    for pos in grid.positions().collect::<Vec<_>>() {
+        if rng.gen_ratio(2, 3) {
+            grid.set(pos, Some(1 << rng.gen_range(1..=5)));
+        }
+    }
+    grid
+}
+
+fn assert_same(grid: &Grid, board: &BitBoard) {
+    assert_eq!(*grid, Grid::from(board), "\n{}", grid);
+    assert_eq!(grid.has_legal_move(), board.has_legal_move(), "\n{}", grid);
+    assert_eq!(
+        grid.empty_positions().collect::<Vec<_>>(),
+        board.empty_positions().collect::<Vec<_>>()
+    );
+    assert_eq!(grid.max_tile(), board.max_tile());
+}
+
+#[test]
+fn shifts_like_grid() {
+    let mut rng = GameRng::new(1);
+    for (width, height) in sizes() {
+        for _ in 0..200 {
+            let grid = random_grid(width, height, &mut rng);
+            let board = BitBoard::try_from(&grid).unwrap();
+            assert_same(&grid, &board);
+            for &direction in Direction::ALL.iter() {
+                let expected = grid.shift(direction);
+                let outcome = board.shift(direction);
+                assert_eq!(
+                    expected.grid,
+                    Grid::from(&outcome.board),
+                    "{:?} on\n{}",
+                    direction,
+                    grid
+                );
+                assert_eq!(
+                    expected.score, outcome.score,
+                    "{:?} on\n{}",
+                    direction, grid
+                );
+                assert_eq!(
+                    expected.moved(),
+                    outcome.moved(),
+                    "{:?} on\n{}",
+                    direction,
+                    grid
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn plays_the_same_games_as_grid() {
+    let rules = SpawnRules::default();
+    for (width, height) in sizes() {
+        let seed = u64::from(width) * 10 + u64::from(height);
+        let mut grid_rng = GameRng::new(seed);
+        let mut board_rng = GameRng::new(seed);
+        let mut choices = GameRng::new(seed + 1000);
+        let mut grid = Grid::new(width, height);
+        let mut board = BitBoard::new(width, height);
+        assert_eq!(
+            rules.spawn(&mut grid, rules.starting_tiles, &mut grid_rng),
+            rules.spawn(&mut board, rules.starting_tiles, &mut board_rng)
+        );
+
+        // two 32768s merge into a tile a BitBoard can't hold
+        while grid.has_legal_move() && grid.max_tile() < Some(1 << 15) {
+            assert_same(&grid, &board);
+            let direction = Direction::ALL[choices.gen_range(0..4)];
+            let expected = grid.shift(direction);
+            let outcome = board.shift(direction);
+            assert_eq!(expected.score, outcome.score);
+            assert_eq!(expected.moved(), outcome.moved());
+            if !expected.moved() {
+                continue;
+            }
+            grid = expected.grid;
+            board = outcome.board;
+            assert_eq!(
+                rules.spawn(&mut grid, rules.tiles_per_turn, &mut grid_rng),
+                rules.spawn(&mut board, rules.tiles_per_turn, &mut board_rng)
+            );
+        }
+        assert_same(&grid, &board);
+    }
+}
+
+#[test]
+fn searches_like_grid() {
+    let ai = Expectimax::new(2, SpawnRules::default());
+    let mut rng = GameRng::new(2);
+    for _ in 0..20 {
+        let grid = random_grid(4, 4, &mut rng);
+        let board = BitBoard::try_from(&grid).unwrap();
+        assert_eq!(ai.move_values(&grid), ai.move_values(&board), "\n{}", grid);
+    }
+}
+
+#[test]
+fn full_boards_only_move_by_merging() {
+    // a checkerboard of 2s and 4s can't move at all
+    let mut grid = Grid::new(4, 4);
+    for pos in grid.positions().collect::<Vec<_>>() {
+        let value = if (pos.x + pos.y) % 2 == 0 { 2 } else { 4 };
+        grid.set(pos, Some(value));
+    }
+    let board = BitBoard::try_from(&grid).unwrap();
+    assert_same(&grid, &board);
+    assert!(!board.has_legal_move());
+
+    grid.set(Position { x: 1, y: 0 }, Some(2));
+    let board = BitBoard::try_from(&grid).unwrap();
+    assert_same(&grid, &board);
+    assert!(board.has_legal_move());
+}
+
+#[test]
+fn rejects_tiles_it_cant_hold() {
+    let mut grid = Grid::new(4, 4);
+    grid.set(Position { x: 0, y: 0 }, Some(3));
+    assert!(BitBoard::try_from(&grid).is_err());
+    grid.set(Position { x: 0, y: 0 }, Some(1 << 16));
+    assert!(BitBoard::try_from(&grid).is_err());
+    grid.set(Position { x: 0, y: 0 }, Some(1 << 15));
+    assert!(BitBoard::try_from(&grid).is_ok());
+}